@@ -0,0 +1,63 @@
+// Suspicious event timing, which almost always points to a bug in the exporting application
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use icalendar::{Component, Event};
+use std::collections::BTreeMap;
+
+use crate::recurrence;
+
+/// Something odd about one event
+pub struct Anomaly<'a> {
+    pub event: &'a Event,
+    pub problem: String,
+}
+
+/// Anomalies of all events: durations that are negative, zero or longer than `max_days`, timed
+/// events from midnight to midnight, and overrides of one series that start at the same time
+pub fn find<'a>(events: &[&'a Event], max_days: i64) -> Vec<Anomaly<'a>> {
+    let mut result = vec![];
+    // Starts of overrides by UID, to find duplicates
+    let mut overrides: BTreeMap<&str, Vec<(DateTime<Utc>, &Event)>> = BTreeMap::new();
+    for &event in events {
+	let mut report = |problem: String| result.push(Anomaly { event, problem });
+	let Some(dtstart) = event.properties().get("DTSTART") else {
+	    continue;
+	};
+	let Some(start) = recurrence::property_date_time(dtstart) else {
+	    continue;
+	};
+	if let (Some(uid), true) = (event.get_uid(), event.properties().contains_key("RECURRENCE-ID")) {
+	    overrides.entry(uid).or_default().push((start.with_timezone(&Utc), event));
+	}
+	// Only explicit ends are suspicious; without DTEND or DURATION, events have a default length
+	let end = if let Some(dtend) = event.properties().get("DTEND") {
+	    recurrence::property_date_time(dtend)
+	} else {
+	    event.property_value("DURATION").and_then(recurrence::parse_duration).map(|duration| start + duration)
+	};
+	let Some(end) = end else {
+	    continue;
+	};
+	let duration = end - start;
+	let midnight = NaiveTime::MIN;
+	if duration < Duration::zero() {
+	    report(format!("ends ({}) before it starts ({})", end.format("%Y-%m-%d %H:%M"), start.format("%Y-%m-%d %H:%M")));
+	} else if duration.is_zero() {
+	    report(format!("ends when it starts ({})", start.format("%Y-%m-%d %H:%M")));
+	} else if duration > Duration::days(max_days) {
+	    report(format!("lasts {} days", duration.num_days()));
+	}
+	if !recurrence::is_date_value(dtstart) && duration > Duration::zero() && start.time() == midnight && end.time() == midnight {
+	    report("runs from midnight to midnight; probably meant to be an all-day event".to_string());
+	}
+    }
+    for starts in overrides.values_mut() {
+	starts.sort_by_key(|(start, event)| (*start, event.property_value("RECURRENCE-ID")));
+	for pair in starts.windows(2).filter(|pair| pair[0].0 == pair[1].0) {
+	    let ((start, first), (_, event)) = (pair[0], pair[1]);
+	    result.push(Anomaly { event, problem: format!("starts at {}, as does the override for {}", start.format("%Y-%m-%d %H:%M UTC"),
+							  first.property_value("RECURRENCE-ID").unwrap_or("")) });
+	}
+    }
+    result
+}
@@ -0,0 +1,121 @@
+// Canonical, diff-friendly serialisation for calendars kept under version control: sorted
+// components and properties, one unfolded line per property, LF line endings, and no volatile
+// bookkeeping properties
+
+use icalendar::{Calendar, CalendarComponent, Component};
+use std::collections::HashSet;
+
+/// Properties that change on every export without any change to the calendar's content
+const VOLATILE_PROPERTIES: &[&str] = &["DTSTAMP", "SEQUENCE"];
+
+/// A component as serialised: its content lines and sub-components
+struct Block {
+    kind: String,
+    lines: Vec<String>,
+    children: Vec<Block>,
+}
+
+impl Block {
+    /// Parse the block that starts with the BEGIN line `lines[*pos]`
+    fn parse(lines: &[&str], pos: &mut usize) -> Block {
+	let kind = lines[*pos].trim_start_matches("BEGIN:").to_string();
+	let mut block = Block { kind, lines: vec![], children: vec![] };
+	*pos += 1;
+	while *pos < lines.len() {
+	    let line = lines[*pos];
+	    if line.starts_with("BEGIN:") {
+		block.children.push(Block::parse(lines, pos));
+		continue;
+	    }
+	    *pos += 1;
+	    if line.starts_with("END:") {
+		break;
+	    }
+	    if !line.is_empty() {
+		block.lines.push(line.to_string());
+	    }
+	}
+	block
+    }
+
+    fn value(&self, name: &str) -> &str {
+	self.lines.iter()
+	    .find(|line| property_name(line) == name)
+	    .and_then(|line| line.split_once(':'))
+	    .map_or("", |(_, value)| value)
+    }
+
+    /// Drop volatile properties, as well as the UIDs that serialisation invents for components that
+    /// have none (VTIMEZONEs, VALARMs, ...); then sort everything
+    fn canonicalize(&mut self, depth: usize, uids: &HashSet<String>) {
+	self.lines.retain(|line| match property_name(line) {
+	    "UID"                                                  => line.split_once(':').is_some_and(|(_, uid)| uids.contains(uid)),
+	    name if VOLATILE_PROPERTIES.contains(&name) && depth > 0 => false,
+	    _                                                      => true,
+	});
+	self.lines.sort_by(|a, b| property_name(a).cmp(property_name(b)).then_with(|| a.cmp(b)));
+	for child in &mut self.children {
+	    child.canonicalize(depth + 1, uids);
+	}
+	if depth == 0 {
+	    // Time zone definitions first, then by kind, UID, RECURRENCE-ID and start
+	    self.children.sort_by_cached_key(|child| (child.kind != "VTIMEZONE", child.kind.clone(), child.value("TZID").to_string(),
+						       child.value("UID").to_string(), child.value("RECURRENCE-ID").to_string(),
+						       child.value("DTSTART").to_string(), child.render()));
+	} else {
+	    self.children.sort_by_cached_key(Block::render);
+	}
+    }
+
+    fn render(&self) -> String {
+	let mut result = format!("BEGIN:{}\n", self.kind);
+	for line in &self.lines {
+	    result += line;
+	    result += "\n";
+	}
+	for child in &self.children {
+	    result += &child.render();
+	}
+	result + &format!("END:{}\n", self.kind)
+    }
+}
+
+/// Name of the property on a content line, e.g. "DTSTART" for "DTSTART;TZID=...:..."
+fn property_name(line: &str) -> &str {
+    line.split([';', ':']).next().unwrap_or(line)
+}
+
+/// UIDs that components really have, rather than get invented when serialised
+fn known_uids(calendar: &Calendar) -> HashSet<String> {
+    fn collect<C: Component>(component: &C, uids: &mut HashSet<String>) {
+	if let Some(uid) = component.property_value("UID") {
+	    uids.insert(uid.to_string());
+	}
+	for child in component.components() {
+	    collect(child, uids);
+	}
+    }
+    let mut uids = HashSet::new();
+    for component in &calendar.components {
+	match component {
+	    CalendarComponent::Event(event) => collect(event, &mut uids),
+	    CalendarComponent::Todo(todo)   => collect(todo, &mut uids),
+	    CalendarComponent::Venue(venue) => collect(venue, &mut uids),
+	    CalendarComponent::Other(other) => collect(other, &mut uids),
+	    _ => {},
+	}
+    }
+    uids
+}
+
+/// The calendar in canonical form, without a final line break
+pub fn git_friendly(calendar: &Calendar) -> String {
+    let unfolded = icalendar::parser::unfold(&calendar.to_string());
+    let lines: Vec<&str> = unfolded.lines().collect();
+    let Some(mut pos) = lines.iter().position(|line| line.starts_with("BEGIN:")) else {
+	return String::new();
+    };
+    let mut block = Block::parse(&lines, &mut pos);
+    block.canonicalize(0, &known_uids(calendar));
+    block.render().trim_end().to_string()
+}
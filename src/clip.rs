@@ -0,0 +1,230 @@
+// Clipping events to a time window, rewriting recurring series rather than expanding them
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Duration, Utc};
+use icalendar::{Component, Event, Property};
+use rrule::{RRuleSet, Tz};
+
+use crate::recurrence;
+
+/// Maximal number of occurrences of a series to consider
+const MAX_OCCURRENCES: u16 = u16::MAX;
+
+/// Half-open time window [from, to); either end may be open
+pub struct Window {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl Window {
+    fn contains(&self, time: DateTime<Utc>) -> bool {
+	self.from.is_none_or(|from| time >= from) && self.to.is_none_or(|to| time < to)
+    }
+
+    /// Starts of the occurrences in the window, in order
+    fn starts(&self, set: &RRuleSet) -> BTreeSet<DateTime<Utc>> {
+	let mut set = set.clone();
+	if let Some(from) = self.from {
+	    set = set.after((from - Duration::seconds(1)).with_timezone(&Tz::UTC));
+	}
+	if let Some(to) = self.to {
+	    set = set.before(to.with_timezone(&Tz::UTC));
+	}
+	set.all(MAX_OCCURRENCES).dates.into_iter()
+	    .map(|start| start.with_timezone(&Utc))
+	    .filter(|start| self.contains(*start))
+	    .collect()
+    }
+}
+
+/// Format a point in time like the value of `property`: as a DATE, in UTC, or in the property's
+/// time zone (or floating)
+fn format_like(property: &Property, time: DateTime<Utc>) -> String {
+    if recurrence::is_date_value(property) {
+	time.with_timezone(&recurrence::property_tz(property)).format("%Y%m%d").to_string()
+    } else if property.value().ends_with('Z') {
+	time.format("%Y%m%dT%H%M%SZ").to_string()
+    } else {
+	time.with_timezone(&recurrence::property_tz(property)).format("%Y%m%dT%H%M%S").to_string()
+    }
+}
+
+/// UNTIL for a rule of an event with this DTSTART: a DATE for all-day events, floating for
+/// floating events, and UTC otherwise (RFC 5545, 3.3.10)
+fn format_until(dtstart: &Property, time: DateTime<Utc>) -> String {
+    if recurrence::is_date_value(dtstart) || (dtstart.params().get("TZID").is_none() && !dtstart.value().ends_with('Z')) {
+	format_like(dtstart, time)
+    } else {
+	time.format("%Y%m%dT%H%M%SZ").to_string()
+    }
+}
+
+/// A property with a new value but the same parameters
+fn with_value(property: &Property, value: &str) -> Property {
+    let mut result = Property::new(property.key(), value);
+    for param in property.params().values() {
+	result.append_parameter(param.clone());
+    }
+    result
+}
+
+/// An EXDATE or RDATE for one occurrence of an event with this DTSTART
+fn date_property(key: &str, dtstart: &Property, time: DateTime<Utc>) -> Property {
+    let mut result = Property::new(key, format_like(dtstart, time));
+    for name in ["TZID", "VALUE"] {
+	if let Some(param) = dtstart.params().get(name) {
+	    result.append_parameter(param.clone());
+	}
+    }
+    result
+}
+
+/// Restrict an event to its occurrences that start within the window; None if none remain.
+/// Recurring series start at their first occurrence in the window and end (UNTIL) at their last.
+pub fn clip_event(event: &Event, window: &Window) -> Option<Event> {
+    let start_of = |property: &Property| recurrence::property_date_time(property).map(|t| t.with_timezone(&Utc));
+    // Overrides of single occurrences stay with the occurrences they replace
+    if let Some(recurrence_id) = event.properties().get("RECURRENCE-ID") {
+	return match start_of(recurrence_id) {
+	    Some(start) if !window.contains(start) => None,
+	    _ => Some(event.clone()),
+	};
+    }
+    let Some(dtstart) = event.properties().get("DTSTART") else {
+	return Some(event.clone());
+    };
+    let rrule = event.property_value("RRULE");
+    let has_rdate = event.multi_properties().contains_key("RDATE") || event.properties().contains_key("RDATE");
+    if rrule.is_none() && !has_rdate {
+	return match start_of(dtstart) {
+	    Some(start) if !window.contains(start) => None,
+	    _ => Some(event.clone()),
+	};
+    }
+    let (Some(set), Some((_, duration, _))) = (recurrence::recurrence_set(event), recurrence::event_timing(event)) else {
+	return Some(event.clone());
+    };
+    let original = window.starts(&set);
+    let (&first, &last) = (original.first()?, original.last()?);
+    // Without an end to the window, only series that end anyway get an UNTIL
+    let finite = window.to.is_some() || rrule.is_none_or(|rule| rule.to_uppercase().contains("COUNT=") || rule.to_uppercase().contains("UNTIL="));
+
+    let mut clipped = crate::map_own_properties(event, |property| match property.key() {
+	"DTSTART" => Some(with_value(property, &format_like(property, first))),
+	"DTEND"   => Some(with_value(property, &format_like(property, first + duration))),
+	"RRULE" if finite => {
+	    let mut parts: Vec<String> = property.value().split(';')
+		.filter(|part| !part.to_uppercase().starts_with("COUNT=") && !part.to_uppercase().starts_with("UNTIL="))
+		.map(str::to_string)
+		.collect();
+	    parts.push(format!("UNTIL={}", format_until(dtstart, last)));
+	    Some(with_value(property, &parts.join(";")))
+	},
+	"EXDATE" | "RDATE" => {
+	    let tz = recurrence::property_tz(property);
+	    let values: Vec<&str> = property.value().split(',')
+		.filter(|value| recurrence::parse_date_time_value(value.trim(), tz)
+			.is_none_or(|time| window.contains(time.with_timezone(&Utc))))
+		.collect();
+	    (!values.is_empty()).then(|| with_value(property, &values.join(",")))
+	},
+	_ => Some(property.clone()),
+    });
+
+    // Moving DTSTART can change what some rules produce; pin down the original occurrences
+    let rewritten = recurrence::recurrence_set(&clipped).map(|set| window.starts(&set)).unwrap_or_default();
+    for &extra in rewritten.difference(&original) {
+	clipped.append_multi_property(date_property("EXDATE", dtstart, extra));
+    }
+    for &missing in original.difference(&rewritten) {
+	clipped.append_multi_property(date_property("RDATE", dtstart, missing));
+    }
+    Some(clipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use icalendar::CalendarComponent;
+
+    fn event(lines: &[&str]) -> Event {
+	let text = format!("BEGIN:VEVENT\r\nUID:test\r\nDTSTAMP:20250101T000000Z\r\n{}\r\nEND:VEVENT\r\n", lines.join("\r\n"));
+	match text.parse::<CalendarComponent>() {
+	    Ok(CalendarComponent::Event(event)) => event,
+	    _ => panic!("cannot parse {}", text),
+	}
+    }
+
+    fn time(text: &str) -> DateTime<Utc> {
+	recurrence::parse_date_time_value(text, Tz::UTC).unwrap().with_timezone(&Utc)
+    }
+
+    fn window(from: &str, to: &str) -> Window {
+	Window { from: Some(time(from)), to: Some(time(to)) }
+    }
+
+    #[test]
+    fn keeps_or_drops_single_events() {
+	let single = event(&["DTSTART:20250303T100000Z", "DTEND:20250303T110000Z"]);
+	assert!(clip_event(&single, &window("20250301T000000Z", "20250401T000000Z")).is_some());
+	assert!(clip_event(&single, &window("20250304T000000Z", "20250401T000000Z")).is_none());
+	// The window is half-open
+	assert!(clip_event(&single, &window("20250301T000000Z", "20250303T100000Z")).is_none());
+	assert!(clip_event(&single, &Window { from: Some(time("20250303T100000Z")), to: None }).is_some());
+    }
+
+    #[test]
+    fn shortens_series_to_window() {
+	let series = event(&["DTSTART;TZID=Europe/Berlin:20250106T090000", "DTEND;TZID=Europe/Berlin:20250106T100000",
+			     "RRULE:FREQ=WEEKLY;BYDAY=MO"]);
+	let clipped = clip_event(&series, &window("20250301T000000Z", "20250401T000000Z")).unwrap();
+	assert_eq!(clipped.property_value("DTSTART"), Some("20250303T090000"));
+	assert_eq!(clipped.property_value("DTEND"), Some("20250303T100000"));
+	// Mondays in March 2025: the 3rd to the 31st, the last in summer time
+	assert_eq!(clipped.property_value("RRULE"), Some("FREQ=WEEKLY;BYDAY=MO;UNTIL=20250331T070000Z"));
+	assert!(!clipped.multi_properties().contains_key("EXDATE"));
+	assert!(!clipped.multi_properties().contains_key("RDATE"));
+    }
+
+    #[test]
+    fn replaces_count_and_keeps_open_ended_series_open() {
+	let counted = event(&["DTSTART:20250101T100000Z", "RRULE:FREQ=DAILY;COUNT=100"]);
+	let clipped = clip_event(&counted, &Window { from: Some(time("20250301T000000Z")), to: None }).unwrap();
+	assert_eq!(clipped.property_value("DTSTART"), Some("20250301T100000Z"));
+	assert_eq!(clipped.property_value("RRULE"), Some("FREQ=DAILY;UNTIL=20250410T100000Z"));
+
+	let endless = event(&["DTSTART:20250101T100000Z", "RRULE:FREQ=DAILY"]);
+	let clipped = clip_event(&endless, &Window { from: Some(time("20250301T000000Z")), to: None }).unwrap();
+	assert_eq!(clipped.property_value("RRULE"), Some("FREQ=DAILY"));
+    }
+
+    #[test]
+    fn uses_dates_for_all_day_series() {
+	let series = event(&["DTSTART;VALUE=DATE:20250101", "RRULE:FREQ=MONTHLY"]);
+	let clipped = clip_event(&series, &window("20250201T000000Z", "20250501T000000Z")).unwrap();
+	assert_eq!(clipped.property_value("DTSTART"), Some("20250201"));
+	assert_eq!(clipped.property_value("RRULE"), Some("FREQ=MONTHLY;UNTIL=20250401"));
+    }
+
+    #[test]
+    fn drops_series_without_occurrences_in_window() {
+	let series = event(&["DTSTART:20250101T100000Z", "RRULE:FREQ=DAILY;COUNT=5"]);
+	assert!(clip_event(&series, &window("20250301T000000Z", "20250401T000000Z")).is_none());
+    }
+
+    #[test]
+    fn drops_exdates_outside_window() {
+	let series = event(&["DTSTART:20250101T100000Z", "RRULE:FREQ=DAILY", "EXDATE:20250102T100000Z,20250305T100000Z"]);
+	let clipped = clip_event(&series, &window("20250301T000000Z", "20250310T000000Z")).unwrap();
+	let exdates: Vec<&str> = clipped.multi_properties().get("EXDATE").into_iter().flatten().map(|p| p.value()).collect();
+	assert_eq!(exdates, vec!["20250305T100000Z"]);
+    }
+
+    #[test]
+    fn keeps_overrides_with_their_occurrences() {
+	let moved = event(&["RECURRENCE-ID:20250303T100000Z", "DTSTART:20250320T100000Z"]);
+	assert!(clip_event(&moved, &window("20250301T000000Z", "20250310T000000Z")).is_some());
+	assert!(clip_event(&moved, &window("20250310T000000Z", "20250401T000000Z")).is_none());
+    }
+}
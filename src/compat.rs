@@ -0,0 +1,209 @@
+// Work-arounds for the quirks of specific calendar importers
+
+use std::collections::HashMap;
+
+use chrono::Duration;
+use clap::ValueEnum;
+use icalendar::{Calendar, CalendarComponent, Component, Event, Property};
+
+use crate::{recurrence, tz};
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Compat {
+    /// Google Calendar
+    Google,
+    /// Microsoft Outlook and Exchange
+    Outlook,
+}
+
+/// Google rejects imported files above this size
+const GOOGLE_MAX_IMPORT_BYTES: usize = 1024 * 1024;
+
+/// Longest UID that Google reliably accepts
+const GOOGLE_MAX_UID_LENGTH: usize = 255;
+
+/// Adapt the calendar to the importer; report all changes on stderr
+pub fn apply(compat: Compat, calendar: &Calendar) -> Calendar {
+    let mut changes = vec![];
+    let result = match compat {
+	Compat::Google  => google(calendar, &mut changes),
+	Compat::Outlook => outlook(calendar, &mut changes),
+    };
+    for change in changes {
+	eprintln!("compat: {}", change);
+    }
+    result
+}
+
+/// Warn about serialised output that the importer will not accept
+pub fn check_size(compat: Compat, text: &str) {
+    if compat == Compat::Google && text.len() > GOOGLE_MAX_IMPORT_BYTES {
+	crate::warn(format!("compat: output has {} KiB, but Google only imports files of up to {} KiB; consider splitting it",
+			    text.len() / 1024, GOOGLE_MAX_IMPORT_BYTES / 1024));
+    }
+}
+
+// --------------------------------------------------------------------------------
+
+fn google(calendar: &Calendar, changes: &mut Vec<String>) -> Calendar {
+    let mut result = Calendar::new();
+    result.properties = calendar.properties.clone();
+    let mut dropped: HashMap<String, usize> = HashMap::new();
+
+    for component in &calendar.components {
+	match component {
+	    CalendarComponent::Event(event) => {
+		result.push(google_event(event, changes));
+	    },
+	    CalendarComponent::Other(other) if other.component_kind() == "VTIMEZONE" => {
+		result.push(component.clone());
+	    },
+	    _ => {
+		// Google ignores (or chokes on) to-dos, journal entries, free/busy information etc.
+		*dropped.entry(crate::component_kind(component)).or_default() += 1;
+	    },
+	}
+    }
+    let mut dropped: Vec<_> = dropped.into_iter().collect();
+    dropped.sort();
+    for (kind, count) in dropped {
+	changes.push(format!("removed {} unsupported {} component(s)", count, kind));
+    }
+
+    google_calendar_metadata(&mut result, changes);
+    result
+}
+
+/// Google reads the calendar name, description and default time zone only from X-WR-* properties
+fn google_calendar_metadata(calendar: &mut Calendar, changes: &mut Vec<String>) {
+    let has = |calendar: &Calendar, key: &str| calendar.properties.iter().any(|p| p.key() == key);
+    for (standard, google) in [("NAME", "X-WR-CALNAME"), ("DESCRIPTION", "X-WR-CALDESC")] {
+	if has(calendar, google) {
+	    continue;
+	}
+	if let Some(text) = calendar.properties.iter().find(|p| p.key() == standard).and_then(crate::escaping::text) {
+	    calendar.append_property(crate::escaping::text_property(google, &text));
+	    changes.push(format!("added {} from {}", google, standard));
+	}
+    }
+    if !has(calendar, "X-WR-TIMEZONE") {
+	// Most commonly used IANA time zone
+	let mut uses: HashMap<String, usize> = HashMap::new();
+	for event in calendar.components.iter().filter_map(|c| c.as_event()) {
+	    let tzid = event.properties().get("DTSTART").and_then(|p| p.params().get("TZID"));
+	    if let Some(tz) = tzid.and_then(|tzid| recurrence::resolve_tz(tzid.value())) {
+		*uses.entry(tz.name().to_string()).or_default() += 1;
+	    }
+	}
+	if let Some((tz, _)) = uses.into_iter().max_by(|(a, m), (b, n)| m.cmp(n).then(b.cmp(a))) {
+	    calendar.append_property(Property::new("X-WR-TIMEZONE", &tz));
+	    changes.push(format!("added X-WR-TIMEZONE:{}", tz));
+	}
+    }
+}
+
+fn google_event(event: &Event, changes: &mut Vec<String>) -> Event {
+    let mut event = event.clone();
+    let uid = event.get_uid().unwrap_or("").to_string();
+
+    // UIDs: Google silently drops events with overlong UIDs and mangles unusual characters
+    let clean: String = uid.chars()
+	.map(|c| if c.is_ascii_graphic() { c } else { '-' })
+	.collect();
+    let clean = if clean.len() > GOOGLE_MAX_UID_LENGTH {
+	format!("{}-{:016x}", &clean[..GOOGLE_MAX_UID_LENGTH - 17], crate::fnv1a(&uid))
+    } else { clean };
+    if clean != uid {
+	changes.push(format!("rewrote UID '{}' as '{}'", uid, clean));
+	event.uid(&clean);
+    }
+
+    // All-day events: Google wants an explicit, exclusive DATE end
+    if let Some((start, duration, true)) = recurrence::event_timing(&event) {
+	let has_end = event.properties().contains_key("DTEND") || event.properties().contains_key("DURATION");
+	if !has_end || duration <= Duration::zero() {
+	    let end = (start + Duration::days(1)).format("%Y%m%d").to_string();
+	    let mut dtend = Property::new("DTEND", &end);
+	    dtend.add_parameter("VALUE", "DATE");
+	    event.append_property(dtend);
+	    changes.push(format!("set DTEND of all-day event '{}' to {}", clean, end));
+	}
+    }
+    event
+}
+
+// --------------------------------------------------------------------------------
+
+fn outlook(calendar: &Calendar, changes: &mut Vec<String>) -> Calendar {
+    let mut result = Calendar::new();
+    result.properties = calendar.properties.clone();
+    result.components = calendar.components.clone();
+
+    // Outlook understands Windows zone names best
+    let mut renaming: HashMap<String, &str> = HashMap::new();
+    for component in result.components.iter_mut() {
+	let Some(tzid) = tz::vtimezone_tzid(component).map(|tzid| tzid.to_string()) else {
+	    continue;
+	};
+	let Some(windows) = recurrence::resolve_tz(&tzid).and_then(tz::windows_name) else {
+	    continue;
+	};
+	if let CalendarComponent::Other(vtimezone) = component {
+	    if tzid != windows {
+		vtimezone.append_property(Property::new("TZID", windows));
+		changes.push(format!("renamed time zone '{}' to '{}'", tzid, windows));
+		renaming.insert(tzid, windows);
+	    }
+	}
+    }
+
+    if !renaming.is_empty() {
+	let rename = |property: &Property| {
+	    let mut property = property.clone();
+	    let windows = property.params().get("TZID").and_then(|tzid| renaming.get(tzid.value()));
+	    if let Some(windows) = windows.copied() {
+		property.add_parameter("TZID", windows);
+	    }
+	    Some(property)
+	};
+	// To-dos, journal entries and alarms may refer to the time zones, too
+	for component in result.components.iter_mut() {
+	    if tz::vtimezone_tzid(component).is_none() {
+		*component = crate::map_component_properties(component, rename);
+	    }
+	}
+    }
+
+    let mut flagged = 0;
+    for component in result.components.iter_mut() {
+	let CalendarComponent::Event(event) = component else {
+	    continue;
+	};
+	// Exchange's own free/busy and all-day markers
+	if !event.properties().contains_key("X-MICROSOFT-CDO-BUSYSTATUS") {
+	    event.add_property("X-MICROSOFT-CDO-BUSYSTATUS", busy_status(event));
+	    flagged += 1;
+	}
+	if !event.properties().contains_key("X-MICROSOFT-CDO-ALLDAYEVENT") {
+	    let all_day = event.properties().get("DTSTART").is_some_and(recurrence::is_date_value);
+	    event.add_property("X-MICROSOFT-CDO-ALLDAYEVENT", if all_day { "TRUE" } else { "FALSE" });
+	}
+    }
+    if flagged > 0 {
+	changes.push(format!("added X-MICROSOFT-CDO-BUSYSTATUS and -ALLDAYEVENT to {} event(s)", flagged));
+    }
+    result
+}
+
+/// Exchange free/busy status of an event, derived from STATUS and TRANSP
+pub fn busy_status(event: &Event) -> &'static str {
+    let status = event.property_value("STATUS").unwrap_or("").to_uppercase();
+    let transp = event.property_value("TRANSP").unwrap_or("").to_uppercase();
+    if transp == "TRANSPARENT" || status == "CANCELLED" {
+	"FREE"
+    } else if status == "TENTATIVE" {
+	"TENTATIVE"
+    } else {
+	"BUSY"
+    }
+}
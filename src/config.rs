@@ -0,0 +1,140 @@
+// Configuration file (TOML), e.g. ~/.icalmrc
+
+use std::{fs::read_to_string, path::Path, sync::OnceLock};
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Credentials for remote calendars
+    #[serde(default)]
+    pub auth: Vec<AuthEntry>,
+    /// Calendars to merge with `cat --sources`
+    #[serde(default)]
+    pub source: Vec<SourceEntry>,
+}
+
+/// A named calendar, given by either `url` or `file`
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SourceEntry {
+    pub name: String,
+    pub url: Option<String>,
+    /// Relative to the directory of the configuration file
+    pub file: Option<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    /// Applied in order to this source only, before merging
+    #[serde(default)]
+    pub transform: Vec<Transform>,
+    /// Overrides of the calendar's own name, description and time zone (X-WR-TIMEZONE)
+    pub calendar_name: Option<String>,
+    pub calendar_description: Option<String>,
+    pub calendar_timezone: Option<String>,
+}
+
+/// A per-source transformation, named like the corresponding command, e.g.
+/// `{ op = "remove-prop", properties = ["ATTENDEE"] }`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "op", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Transform {
+    RemoveProp { properties: Vec<String> },
+    KeepProp { properties: Vec<String> },
+    SetProp { property: String, value: String },
+    PrefixProp { property: String, prefix: String },
+    TzSubst { from: String, to: String },
+    FixEscaping { #[serde(default)] newlines: bool },
+    SplitValues { properties: Vec<String> },
+    JoinValues { properties: Vec<String> },
+    /// Retention periods such as "90d"; "all" if not given
+    #[serde(rename_all = "kebab-case")]
+    Retain { keep_past: Option<String>, keep_future: Option<String> },
+    TzMerge,
+    TzReplaceDefinitions,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// Credentials for `url` and all URLs below it (on the same scheme, host and port, and below its
+/// path, e.g. "https://example.com/cal" and "https://example.com/cal/work.ics").  Values may refer to environment variables as
+/// ${NAME}, to keep secrets out of the file; they are expanded when used.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AuthEntry {
+    pub url: String,
+    /// "user:password" for HTTP basic authentication
+    pub basic: Option<String>,
+    /// Token for "Authorization: Bearer"
+    pub bearer: Option<String>,
+    /// Query parameters to append, e.g. "token=${FEED_TOKEN}"
+    pub query: Option<String>,
+}
+
+/// Replace ${NAME} by the value of environment variable NAME
+pub fn expand_env(text: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+	result.push_str(&rest[..start]);
+	let end = rest[start..].find('}').ok_or_else(|| format!("unterminated '${{' in '{}'", text))?;
+	let name = &rest[start + 2..start + end];
+	result += &std::env::var(name).map_err(|_| format!("environment variable {} is not set", name))?;
+	rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, String> {
+	toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Files and URLs of all enabled sources; `filename` is the configuration file itself
+    pub fn source_locations(&self, filename: &str) -> Result<Vec<(String, &SourceEntry)>, String> {
+	let directory = Path::new(filename).parent().unwrap_or(Path::new(""));
+	let mut locations = vec![];
+	for source in self.source.iter().filter(|source| source.enabled) {
+	    match (&source.url, &source.file) {
+		(Some(url), None)  => locations.push((url.clone(), source)),
+		(None, Some(file)) => locations.push((directory.join(file).to_string_lossy().into_owned(), source)),
+		_                  => return Err(format!("source '{}' needs exactly one of url, file", source.name)),
+	    }
+	}
+	Ok(locations)
+    }
+
+    /// Load the given file, or else ~/.icalmrc if it exists
+    pub fn load(filename: Option<&str>) -> Result<Self, String> {
+	let filename = match filename {
+	    Some(filename) => filename.to_string(),
+	    None           => match std::env::var("HOME") {
+		Ok(home) if Path::new(&home).join(".icalmrc").exists() => format!("{}/.icalmrc", home),
+		_ => return Ok(Config::default()),
+	    },
+	};
+	let text = read_to_string(&filename).map_err(|e| format!("{}: {}", filename, e))?;
+	Self::parse(&text).map_err(|e| format!("{}: {}", filename, e))
+    }
+}
+
+/// A configuration file that is only loaded when first needed, so that a broken one does not stop
+/// commands that fetch nothing
+pub struct Deferred {
+    filename: Option<String>,
+    config: OnceLock<Result<Config, String>>,
+}
+
+impl Deferred {
+    /// The given file, or else ~/.icalmrc if it exists
+    pub fn new(filename: Option<&str>) -> Self {
+	Deferred { filename: filename.map(str::to_string), config: OnceLock::new() }
+    }
+
+    pub fn get(&self) -> Result<&Config, String> {
+	self.config.get_or_init(|| Config::load(self.filename.as_deref())).as_ref().map_err(|e| e.clone())
+    }
+}
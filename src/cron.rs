@@ -0,0 +1,199 @@
+// Recurring events from cron-style schedules, e.g. "0 9 * * MON-FRI"
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use icalendar::{Component, Event, Property};
+use rrule::{RRule, RRuleSet, Tz, Unvalidated};
+
+use crate::recurrence;
+
+const MONTH_NAMES: &[&str] = &["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+/// Day-of-week names, indexed by cron's numbering (0 = Sunday)
+const DAY_NAMES: &[&str] = &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+const RRULE_DAY_NAMES: &[&str] = &["SU", "MO", "TU", "WE", "TH", "FR", "SA"];
+
+/// Nonstandard cron shorthands
+const MACROS: &[(&str, &str)] = &[("@yearly", "0 0 1 1 *"), ("@annually", "0 0 1 1 *"), ("@monthly", "0 0 1 * *"),
+				  ("@weekly", "0 0 * * 0"), ("@daily", "0 0 * * *"), ("@midnight", "0 0 * * *"),
+				  ("@hourly", "0 * * * *")];
+
+/// Number or (three-letter) name of a field value; names start counting at `min`
+fn parse_value(value: &str, min: u32, names: &[&str]) -> Option<u32> {
+    match names.iter().position(|n| n.eq_ignore_ascii_case(value)) {
+	Some(index) => Some(min + index as u32),
+	None        => value.parse().ok(),
+    }
+}
+
+/// Values of one field, sorted, or None for "*" (all values)
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<Option<Vec<u32>>, String> {
+    if field == "*" {
+	return Ok(None);
+    }
+    let mut values = vec![];
+    for item in field.split(',') {
+	let (range, step) = match item.split_once('/') {
+	    Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)
+				    .ok_or_else(|| format!("invalid step in '{}'", item))?),
+	    None                => (item, 1),
+	};
+	let (first, last) = if range == "*" {
+	    (min, max)
+	} else if let Some((first, last)) = range.split_once('-') {
+	    (parse_value(first, min, names).ok_or_else(|| format!("invalid value '{}'", first))?,
+	     parse_value(last, min, names).ok_or_else(|| format!("invalid value '{}'", last))?)
+	} else {
+	    let value = parse_value(range, min, names).ok_or_else(|| format!("invalid value '{}'", range))?;
+	    // "5/15" means "5-max/15"
+	    (value, if item.contains('/') { max } else { value })
+	};
+	if first < min || last > max || first > last {
+	    return Err(format!("'{}' is out of range {}-{}", item, min, max));
+	}
+	values.extend((first..=last).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(Some(values))
+}
+
+fn join(values: &[u32]) -> String {
+    values.iter().map(u32::to_string).collect::<Vec<_>>().join(",")
+}
+
+/// Translate a five-field cron expression (minute, hour, day of month, month, day of week) into
+/// an RRULE value
+pub fn rrule(expression: &str) -> Result<String, String> {
+    let expression = MACROS.iter().find(|(name, _)| name.eq_ignore_ascii_case(expression.trim()))
+	.map_or(expression, |(_, expansion)| expansion);
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+	return Err(format!("expected five fields (minute hour day month weekday), got {}", fields.len()));
+    };
+    let minutes = parse_field(minute, 0, 59, &[])?;
+    let hours = parse_field(hour, 0, 23, &[])?;
+    let days = parse_field(day, 1, 31, &[])?;
+    let months = parse_field(month, 1, 12, MONTH_NAMES)?;
+    // Both 0 and 7 are Sunday
+    let weekdays = parse_field(weekday, 0, 7, DAY_NAMES)?
+	.map(|w| { let mut w: Vec<u32> = w.into_iter().map(|d| d % 7).collect(); w.sort_unstable(); w.dedup(); w });
+    if days.is_some() && weekdays.is_some() {
+	return Err("cron runs when either the day of month or the day of week matches, which RRULE cannot express; \
+		    restrict only one of them".to_string());
+    }
+
+    let frequency = if minutes.is_none() {
+	"MINUTELY"
+    } else if hours.is_none() {
+	"HOURLY"
+    } else {
+	"DAILY"
+    };
+    let mut parts = vec![format!("FREQ={}", frequency)];
+    if let Some(months) = months {
+	parts.push(format!("BYMONTH={}", join(&months)));
+    }
+    if let Some(days) = days {
+	parts.push(format!("BYMONTHDAY={}", join(&days)));
+    }
+    if let Some(weekdays) = weekdays {
+	parts.push(format!("BYDAY={}", weekdays.iter().map(|&d| RRULE_DAY_NAMES[d as usize]).collect::<Vec<_>>().join(",")));
+    }
+    if let Some(hours) = hours {
+	parts.push(format!("BYHOUR={}", join(&hours)));
+    }
+    if let Some(minutes) = minutes {
+	parts.push(format!("BYMINUTE={}", join(&minutes)));
+    }
+    Ok(parts.join(";"))
+}
+
+/// Recurring event for a cron expression, starting with its first run on or after `from`, in
+/// the time zone `tzid` (floating local time if None)
+pub fn schedule_event(expression: &str, summary: &str, duration: Duration, tzid: Option<&str>, from: NaiveDate) -> Result<Event, String> {
+    let rrule = rrule(expression)?;
+    let tz: Tz = match tzid {
+	Some(tzid) => recurrence::resolve_tz(tzid).ok_or_else(|| format!("unknown time zone '{}'", tzid))?.into(),
+	None       => Tz::LOCAL,
+    };
+    // DTSTART must be an occurrence itself, so find the first run
+    let anchor = tz.from_local_datetime(&from.and_hms_opt(0, 0, 0).unwrap()).earliest()
+	.ok_or_else(|| format!("{} does not start at midnight in this time zone", from))?;
+    let validated = rrule.parse::<RRule<Unvalidated>>().and_then(|r| r.validate(anchor)).map_err(|e| e.to_string())?;
+    let first = *RRuleSet::new(anchor).rrule(validated).all(1).dates.first()
+	.ok_or_else(|| format!("'{}' never runs", expression))?;
+
+    let mut event = Event::new();
+    event.uid(&format!("{:016x}@icalm", crate::fnv1a(&format!("{}\u{1f}{}\u{1f}{}", expression, summary, first))));
+    event.summary(summary);
+    event.timestamp(Utc::now());
+    let mut dtstart = Property::new("DTSTART", first.format("%Y%m%dT%H%M%S").to_string());
+    if let Some(tzid) = tzid {
+	dtstart.add_parameter("TZID", tzid);
+    }
+    event.append_property(dtstart);
+    event.add_property("DURATION", recurrence::format_duration(duration));
+    event.add_property("RRULE", &rrule);
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lists_ranges_steps_and_names() {
+	assert_eq!(parse_field("*", 0, 59, &[]), Ok(None));
+	assert_eq!(parse_field("5,1,5", 0, 59, &[]), Ok(Some(vec![1, 5])));
+	assert_eq!(parse_field("*/15", 0, 59, &[]), Ok(Some(vec![0, 15, 30, 45])));
+	assert_eq!(parse_field("50/5", 0, 59, &[]), Ok(Some(vec![50, 55])));
+	assert_eq!(parse_field("9-17/4", 0, 23, &[]), Ok(Some(vec![9, 13, 17])));
+	assert_eq!(parse_field("mon-WED", 0, 7, DAY_NAMES), Ok(Some(vec![1, 2, 3])));
+	assert_eq!(parse_field("Dec", 1, 12, MONTH_NAMES), Ok(Some(vec![12])));
+	assert!(parse_field("60", 0, 59, &[]).is_err());
+	assert!(parse_field("5-1", 0, 59, &[]).is_err());
+	assert!(parse_field("*/0", 0, 59, &[]).is_err());
+	assert!(parse_field("MON", 0, 59, &[]).is_err());
+    }
+
+    #[test]
+    fn translates_expressions_into_rrules() {
+	assert_eq!(rrule("0 9 * * MON-FRI").as_deref(), Ok("FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR;BYHOUR=9;BYMINUTE=0"));
+	assert_eq!(rrule("30 8 1,15 * *").as_deref(), Ok("FREQ=DAILY;BYMONTHDAY=1,15;BYHOUR=8;BYMINUTE=30"));
+	assert_eq!(rrule("0 * * JAN,jul *").as_deref(), Ok("FREQ=HOURLY;BYMONTH=1,7;BYMINUTE=0"));
+	assert_eq!(rrule("* * * * *").as_deref(), Ok("FREQ=MINUTELY"));
+	// Both 0 and 7 are Sunday
+	assert_eq!(rrule("0 0 * * 0,7").as_deref(), Ok("FREQ=DAILY;BYDAY=SU;BYHOUR=0;BYMINUTE=0"));
+	assert_eq!(rrule("@weekly"), rrule("0 0 * * 0"));
+	assert_eq!(rrule(" @Daily ").as_deref(), Ok("FREQ=DAILY;BYHOUR=0;BYMINUTE=0"));
+    }
+
+    #[test]
+    fn rejects_what_rrule_cannot_express() {
+	assert!(rrule("0 9 * *").is_err());
+	assert!(rrule("0 9 * * * *").is_err());
+	assert!(rrule("0 9 1 * MON").is_err());
+	assert!(rrule("0 25 * * *").is_err());
+    }
+
+    #[test]
+    fn starts_schedules_at_the_first_run() {
+	// 2025-03-01 is a Saturday
+	let from = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+	let event = schedule_event("30 9 * * MON-FRI", "Stand-up", Duration::minutes(15), Some("Europe/Berlin"), from).unwrap();
+	let dtstart = event.properties().get("DTSTART").unwrap();
+	assert_eq!(dtstart.value(), "20250303T093000");
+	assert_eq!(dtstart.params().get("TZID").map(|tzid| tzid.value()), Some("Europe/Berlin"));
+	assert_eq!(event.property_value("DURATION"), Some("PT15M"));
+	assert_eq!(event.property_value("RRULE"), Some("FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR;BYHOUR=9;BYMINUTE=30"));
+	// The same schedule gets the same UID
+	let again = schedule_event("30 9 * * MON-FRI", "Stand-up", Duration::minutes(15), Some("Europe/Berlin"), from).unwrap();
+	assert_eq!(event.get_uid(), again.get_uid());
+    }
+
+    #[test]
+    fn rejects_unknown_time_zones_and_schedules_that_never_run() {
+	let from = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+	assert!(schedule_event("0 9 * * *", "x", Duration::hours(1), Some("Mars/Olympus"), from).is_err());
+	assert!(schedule_event("0 9 31 2 *", "x", Duration::hours(1), Some("UTC"), from).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+// Events from spreadsheets (CSV), following a user-supplied column-to-property mapping
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use icalendar::{Component, Event, Property};
+
+/// Properties whose values are DATE or DATE-TIME
+pub const DATE_TIME_PROPERTIES: &[&str] = &["DTSTART", "DTEND", "DUE", "RECURRENCE-ID"];
+
+/// Formats tried for date-time columns without an explicit format
+const DEFAULT_DATE_TIME_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M",
+					     "%Y-%m-%dT%H:%M", "%Y%m%dT%H%M%S"];
+const DEFAULT_DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y%m%d"];
+
+/// One "Column=PROPERTY{format}" entry of the mapping
+pub struct ColumnMapping {
+    column: String,
+    property: String,
+    format: Option<String>,
+}
+
+/// Parse a mapping such as "Title=SUMMARY,Start=DTSTART{%d/%m/%Y %H:%M},Room=LOCATION"
+pub fn parse_mapping(spec: &str) -> Result<Vec<ColumnMapping>, String> {
+    // Split at commas outside of {...}
+    let mut entries = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in spec.char_indices() {
+	match c {
+	    '{' => depth += 1,
+	    '}' => depth -= 1,
+	    ',' if depth == 0 => {
+		entries.push(&spec[start..i]);
+		start = i + 1;
+	    },
+	    _ => {},
+	}
+    }
+    entries.push(&spec[start..]);
+
+    let mut mapping = vec![];
+    for entry in entries.into_iter().map(str::trim).filter(|e| !e.is_empty()) {
+	let (column, target) = entry.split_once('=')
+	    .ok_or_else(|| format!("'{}' is not of the form Column=PROPERTY", entry))?;
+	let (property, format) = match target.split_once('{') {
+	    Some((property, format)) => {
+		let format = format.strip_suffix('}').ok_or_else(|| format!("Unterminated format in '{}'", entry))?;
+		(property, Some(format.to_string()))
+	    },
+	    None => (target, None),
+	};
+	mapping.push(ColumnMapping {
+	    column: column.trim().to_string(),
+	    property: property.trim().to_uppercase(),
+	    format,
+	});
+    }
+    Ok(mapping)
+}
+
+/// DATE or DATE-TIME property from a (possibly combined) cell value, local to `tzid` if given
+pub fn date_time_property(key: &str, value: &str, format: Option<&str>, tzid: Option<&str>) -> Option<Property> {
+    let date_time = match format {
+	Some(format) => NaiveDateTime::parse_from_str(value, format).ok(),
+	None         => DEFAULT_DATE_TIME_FORMATS.iter().find_map(|f| NaiveDateTime::parse_from_str(value, f).ok()),
+    };
+    if let Some(date_time) = date_time {
+	let mut property = Property::new(key, date_time.format("%Y%m%dT%H%M%S").to_string());
+	if let Some(tzid) = tzid {
+	    property.add_parameter("TZID", tzid);
+	}
+	return Some(property);
+    }
+    let date = match format {
+	Some(format) => NaiveDate::parse_from_str(value, format).ok(),
+	None         => DEFAULT_DATE_FORMATS.iter().find_map(|f| NaiveDate::parse_from_str(value, f).ok()),
+    }?;
+    let mut property = Property::new(key, date.format("%Y%m%d").to_string());
+    property.add_parameter("VALUE", "DATE");
+    Some(property)
+}
+
+/// Events for all rows of a CSV text; rows without a usable DTSTART are skipped
+pub fn import(text: &str, mapping: &[ColumnMapping], delimiter: u8, tzid: Option<&str>) -> Result<Vec<Event>, String> {
+    let mut reader = csv::ReaderBuilder::new().delimiter(delimiter).flexible(true).from_reader(text.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let mut columns = vec![];
+    for entry in mapping {
+	let index = headers.iter().position(|h| h.trim() == entry.column)
+	    .ok_or_else(|| format!("No column '{}' (columns are: {})", entry.column, headers.iter().collect::<Vec<_>>().join(", ")))?;
+	columns.push(index);
+    }
+
+    let mut events = vec![];
+    for (row, record) in reader.records().enumerate() {
+	let record = record.map_err(|e| e.to_string())?;
+	// Columns mapped to the same property (e.g., separate date and time) are joined by spaces
+	let mut values: Vec<(&str, Vec<&str>, Vec<&str>)> = vec![];
+	for (entry, &index) in mapping.iter().zip(&columns) {
+	    let cell = record.get(index).unwrap_or("").trim();
+	    if cell.is_empty() {
+		continue;
+	    }
+	    let position = match values.iter().position(|(p, _, _)| *p == entry.property) {
+		Some(position) => position,
+		None           => { values.push((&entry.property, vec![], vec![])); values.len() - 1 },
+	    };
+	    values[position].1.push(cell);
+	    if let Some(ref format) = entry.format {
+		values[position].2.push(format);
+	    }
+	}
+
+	let mut event = Event::new();
+	for (key, cells, formats) in values {
+	    let value = cells.join(" ");
+	    if DATE_TIME_PROPERTIES.contains(&key) || !formats.is_empty() {
+		let format = (!formats.is_empty()).then(|| formats.join(" "));
+		match date_time_property(key, &value, format.as_deref(), tzid) {
+		    Some(property) => { event.append_property(property); },
+		    None           => crate::warn(format!("Row {}: cannot read {} '{}' as a date or date-time", row + 2, key, value)),
+		}
+	    } else {
+		event.add_property(key, &value);
+	    }
+	}
+	if !event.properties().contains_key("DTSTART") {
+	    crate::warn(format!("Row {}: no start; skipping", row + 2));
+	    continue;
+	}
+	if !event.properties().contains_key("UID") {
+	    let uid = format!("{:016x}@icalm", crate::fnv1a(&record.iter().collect::<Vec<_>>().join("\u{1f}")));
+	    event.add_property("UID", &uid);
+	}
+	event.timestamp(Utc::now());
+	events.push(event);
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mapping_with_formats() {
+	let mapping = parse_mapping("Title=summary, Start=DTSTART{%d/%m/%Y, %H:%M},Room=LOCATION").unwrap();
+	let entries: Vec<(&str, &str, Option<&str>)> = mapping.iter()
+	    .map(|entry| (entry.column.as_str(), entry.property.as_str(), entry.format.as_deref()))
+	    .collect();
+	assert_eq!(entries, vec![("Title", "SUMMARY", None), ("Start", "DTSTART", Some("%d/%m/%Y, %H:%M")), ("Room", "LOCATION", None)]);
+	assert!(parse_mapping("Title").is_err());
+	assert!(parse_mapping("Start=DTSTART{%Y").is_err());
+    }
+
+    #[test]
+    fn reads_quoted_cells_with_commas() {
+	let mapping = parse_mapping("Title=SUMMARY,Start=DTSTART").unwrap();
+	let events = import("Title,Start\n\"Lunch, then coffee\",2025-03-03 12:00\n", &mapping, b',', None).unwrap();
+	assert_eq!(events.len(), 1);
+	assert_eq!(events[0].get_summary(), Some("Lunch, then coffee"));
+	assert_eq!(events[0].property_value("DTSTART"), Some("20250303T120000"));
+    }
+
+    #[test]
+    fn joins_date_and_time_columns() {
+	let mapping = parse_mapping("Title=SUMMARY,Day=DTSTART{%d.%m.%Y},Time=DTSTART{%H:%M}").unwrap();
+	let events = import("Title;Day;Time\nExam;03.03.2025;09:30\n", &mapping, b';', Some("Europe/Berlin")).unwrap();
+	let dtstart = events[0].properties().get("DTSTART").unwrap();
+	assert_eq!(dtstart.value(), "20250303T093000");
+	assert_eq!(dtstart.params().get("TZID").map(|tzid| tzid.value()), Some("Europe/Berlin"));
+    }
+
+    #[test]
+    fn reads_dates_as_all_day() {
+	let property = date_time_property("DTSTART", "2025-03-03", None, Some("Europe/Berlin")).unwrap();
+	assert_eq!(property.value(), "20250303");
+	assert_eq!(property.params().get("VALUE").map(|value| value.value()), Some("DATE"));
+	assert!(property.params().get("TZID").is_none());
+	assert!(date_time_property("DTSTART", "soon", None, None).is_none());
+    }
+
+    #[test]
+    fn skips_rows_without_start_and_reports_missing_columns() {
+	let mapping = parse_mapping("Title=SUMMARY,Start=DTSTART").unwrap();
+	let events = import("Title,Start\nA,2025-03-03\nB,\n", &mapping, b',', None).unwrap();
+	assert_eq!(events.iter().map(|event| event.get_summary()).collect::<Vec<_>>(), vec![Some("A")]);
+	let error = import("Title,Begin\nA,2025-03-03\n", &mapping, b',', None).err().unwrap();
+	assert!(error.contains("No column 'Start'"), "{}", error);
+    }
+
+    #[test]
+    fn derives_stable_uids() {
+	let mapping = parse_mapping("Title=SUMMARY,Start=DTSTART").unwrap();
+	let text = "Title,Start\nA,2025-03-03\n";
+	let uid = |events: Vec<Event>| events[0].get_uid().map(str::to_string);
+	assert_eq!(uid(import(text, &mapping, b',', None).unwrap()), uid(import(text, &mapping, b',', None).unwrap()));
+    }
+}
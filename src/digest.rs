@@ -0,0 +1,186 @@
+// Schedule digests: the events of a period rendered as plain text, HTML or a complete email message
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use icalendar::{Component, Event, EventLike};
+
+use crate::{locale::Locale, recurrence::Occurrence};
+
+/// Maximal number of occurrences of a single event to list in a digest
+const MAX_OCCURRENCES: u16 = 1000;
+
+/// Length of base64 lines in email bodies (RFC 2045)
+const BASE64_LINE_LENGTH: usize = 76;
+
+/// Occurrences within a period, grouped by (local) day
+pub struct Digest<'a> {
+    pub title: String,
+    pub days: Vec<(NaiveDate, Vec<(Occurrence, &'a Event)>)>,
+    pub locale: Locale,
+}
+
+impl<'a> Digest<'a> {
+    /// Collect the occurrences of all (non-cancelled) events between `from` and `until`
+    pub fn new(title: String, events: impl Iterator<Item = &'a Event>, from: DateTime<Utc>, until: DateTime<Utc>, locale: Locale) -> Self {
+	let events: Vec<&Event> = events.collect();
+	// Occurrences that overrides move elsewhere are listed as the overrides
+	let overridden = crate::recurrence::overridden(events.iter().copied());
+	let mut occurrences: Vec<(Occurrence, &Event)> = vec![];
+	for event in events {
+	    if event.property_value("STATUS").is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED")) {
+		continue;
+	    }
+	    for occurrence in crate::recurrence::occurrences(event, from, Some(until), MAX_OCCURRENCES) {
+		if !crate::recurrence::is_overridden(&overridden, event, &occurrence) {
+		    occurrences.push((occurrence, event));
+		}
+	    }
+	}
+	// All-day events first on each day
+	occurrences.sort_by_key(|(occurrence, _)| (local_date(occurrence), !occurrence.all_day, occurrence.start.with_timezone(&Utc)));
+	let mut days: Vec<(NaiveDate, Vec<(Occurrence, &Event)>)> = vec![];
+	for (occurrence, event) in occurrences {
+	    let date = local_date(&occurrence).max(from.with_timezone(&Local).date_naive());
+	    match days.last_mut() {
+		Some((last, entries)) if *last == date => entries.push((occurrence, event)),
+		_ => days.push((date, vec![(occurrence, event)])),
+	    }
+	}
+	Digest { title, days, locale }
+    }
+
+    /// UIDs of all events listed
+    pub fn uids(&self) -> Vec<&str> {
+	let mut uids: Vec<&str> = self.days.iter()
+	    .flat_map(|(_, entries)| entries.iter().filter_map(|(_, event)| event.get_uid()))
+	    .collect();
+	uids.sort();
+	uids.dedup();
+	uids
+    }
+
+    pub fn plain_text(&self) -> String {
+	let mut result = format!("{}\n{}\n", self.title, "=".repeat(self.title.chars().count()));
+	if self.days.is_empty() {
+	    result += &format!("\n{}\n", self.locale.no_events());
+	}
+	for (date, entries) in &self.days {
+	    result += &format!("\n{}\n", self.locale.date(*date));
+	    for (occurrence, event) in entries {
+		result += &format!("  {:<13}  {}\n", describe_time(occurrence, &self.locale), event.get_summary().unwrap_or(self.locale.no_summary()));
+		if let Some(location) = event.get_location() {
+		    result += &format!("  {:<13}  {}\n", "", location);
+		}
+	    }
+	}
+	result
+    }
+
+    pub fn html(&self) -> String {
+	let escape = crate::html_escape;
+	let mut result = format!("<html><body>\n<h1>{}</h1>\n", escape(&self.title));
+	if self.days.is_empty() {
+	    result += &format!("<p>{}</p>\n", escape(self.locale.no_events()));
+	}
+	for (date, entries) in &self.days {
+	    result += &format!("<h2>{}</h2>\n<table>\n", escape(&self.locale.date(*date)));
+	    for (occurrence, event) in entries {
+		let mut what = format!("<b>{}</b>", escape(event.get_summary().unwrap_or(self.locale.no_summary())));
+		if let Some(location) = event.get_location() {
+		    what += &format!("<br>{}", escape(location));
+		}
+		result += &format!("<tr><td>{}</td><td>{}</td></tr>\n", escape(&describe_time(occurrence, &self.locale)), what);
+	    }
+	    result += "</table>\n";
+	}
+	result + "</body></html>\n"
+    }
+}
+
+/// Day on which an occurrence starts, in local time
+fn local_date(occurrence: &Occurrence) -> NaiveDate {
+    if occurrence.all_day {
+	occurrence.start.date_naive()
+    } else {
+	occurrence.start.with_timezone(&Local).date_naive()
+    }
+}
+
+/// "all day" or "09:00-10:30", in local time
+pub fn describe_time(occurrence: &Occurrence, locale: &Locale) -> String {
+    if occurrence.all_day {
+	return locale.all_day().to_string();
+    }
+    let start = occurrence.start.with_timezone(&Local);
+    let end = occurrence.end().with_timezone(&Local);
+    let (start_time, end_time) = (locale.time(start.time()), locale.time(end.time()));
+    if occurrence.duration.is_zero() {
+	start_time
+    } else if end.date_naive() == start.date_naive() {
+	format!("{}-{}", start_time, end_time)
+    } else {
+	format!("{}-{} {}", start_time, locale.weekday_abbreviation(end.weekday()), end_time)
+    }
+}
+
+// --------------------------------------------------------------------------------
+
+/// Header fields of a digest email
+pub struct Envelope<'a> {
+    pub sender: Option<&'a str>,
+    pub recipients: &'a [String],
+    pub subject: &'a str,
+}
+
+/// Encode a header value as an RFC 2047 encoded word, if it is not plain ASCII
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+	value.to_string()
+    } else {
+	format!("=?UTF-8?B?{}?=", BASE64_STANDARD.encode(value))
+    }
+}
+
+/// Base64-encode a body, in lines of the permitted length
+fn encode_body(body: &str) -> String {
+    let encoded = BASE64_STANDARD.encode(body);
+    encoded.as_bytes().chunks(BASE64_LINE_LENGTH)
+	.map(|line| String::from_utf8_lossy(line) + "\r\n")
+	.collect()
+}
+
+/// A complete RFC 5322 message with plain-text and HTML alternatives and, optionally, an .ics
+/// attachment
+pub fn mail_message(envelope: &Envelope, digest: &Digest, attachment: Option<&str>) -> String {
+    let (text, html) = (digest.plain_text(), digest.html());
+    let boundary = format!("icalm-{:016x}", crate::fnv1a(&format!("{}\u{1f}{}", text, attachment.unwrap_or(""))));
+    let mut message = String::new();
+    if let Some(sender) = envelope.sender {
+	message += &format!("From: {}\r\n", sender);
+    }
+    if !envelope.recipients.is_empty() {
+	message += &format!("To: {}\r\n", envelope.recipients.join(", "));
+    }
+    message += &format!("Subject: {}\r\n", encode_header(envelope.subject));
+    message += &format!("Date: {}\r\n", Local::now().to_rfc2822());
+    message += "MIME-Version: 1.0\r\n";
+    message += &format!("Content-Type: multipart/mixed; boundary=\"{}-mixed\"\r\n\r\n", boundary);
+
+    message += &format!("--{}-mixed\r\n", boundary);
+    message += &format!("Content-Type: multipart/alternative; boundary=\"{}-alt\"\r\n\r\n", boundary);
+    for (content_type, body) in [("text/plain", text), ("text/html", html)] {
+	message += &format!("--{}-alt\r\n", boundary);
+	message += &format!("Content-Type: {}; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n", content_type);
+	message += &encode_body(&body);
+    }
+    message += &format!("--{}-alt--\r\n", boundary);
+
+    if let Some(calendar) = attachment {
+	message += &format!("--{}-mixed\r\n", boundary);
+	message += "Content-Type: text/calendar; charset=UTF-8\r\n";
+	message += "Content-Disposition: attachment; filename=\"schedule.ics\"\r\n";
+	message += "Content-Transfer-Encoding: base64\r\n\r\n";
+	message += &encode_body(calendar);
+    }
+    message + &format!("--{}-mixed--\r\n", boundary)
+}
@@ -0,0 +1,205 @@
+// TEXT values that were escaped twice by their producer.  Once parsed, a correct TEXT value
+// contains no escape sequences; one that still contains "\n", "\," etc. shows them literally in
+// clients, and a literal "\," may turn one value into two for clients that split on commas.
+// Backslashes also occur in correct values, e.g. in "C:\new" or LaTeX, so only values that look
+// escaped throughout count as escaped twice: no bare commas, semicolons or line breaks, and no
+// backslash that does not start an escape sequence.
+
+use icalendar::{Calendar, Property, ValueType};
+
+/// Escape sequences of TEXT values (RFC 5545, 3.3.11) and what they stand for
+const SEQUENCES: [(&str, char); 5] = [("\\n", '\n'), ("\\N", '\n'), ("\\,", ','), ("\\;", ';'), ("\\\\", '\\')];
+
+/// Calendar properties that hold TEXT but that the icalendar crate neither unescapes when parsing
+/// nor escapes when writing, as it does not know their type
+const UNTYPED_TEXT: [&str; 3] = ["NAME", "X-WR-CALNAME", "X-WR-CALDESC"];
+
+/// The text of a TEXT property, unescaped; None for other properties
+pub fn text(property: &Property) -> Option<String> {
+    if UNTYPED_TEXT.contains(&property.key()) {
+	Some(unescape(property.value()))
+    } else if matches!(property.value_type(), Some(ValueType::Text)) {
+	Some(property.value().to_string())
+    } else {
+	None
+    }
+}
+
+/// A property with the given text, escaped if the icalendar crate would not do so
+pub fn text_property(key: &str, text: &str) -> Property {
+    if UNTYPED_TEXT.contains(&key) {
+	Property::new(key, escape(text))
+    } else {
+	Property::new(key, text)
+    }
+}
+
+/// Split an unescaped TEXT value into escape sequences and other text; None if it contains a
+/// backslash that does not start an escape sequence
+fn tokens(text: &str) -> Option<Vec<Result<(&'static str, char), &str>>> {
+    let mut result = vec![];
+    let mut rest = text;
+    while let Some(i) = rest.find('\\') {
+	if i > 0 {
+	    result.push(Err(&rest[..i]));
+	}
+	let (sequence, c) = SEQUENCES.iter().find(|(sequence, _)| rest[i..].starts_with(sequence))?;
+	result.push(Ok((*sequence, *c)));
+	rest = &rest[i + sequence.len()..];
+    }
+    if !rest.is_empty() {
+	result.push(Err(rest));
+    }
+    Some(result)
+}
+
+/// The first escape sequence that occurs literally in an unescaped TEXT value that looks escaped
+/// twice, if any
+pub fn literal_escape(text: &str) -> Option<&'static str> {
+    let tokens = tokens(text)?;
+    if tokens.iter().any(|token| token.is_err_and(|text| text.contains([',', ';', '\n']))) {
+	return None;
+    }
+    tokens.into_iter().find_map(Result::ok).map(|(sequence, _)| sequence)
+}
+
+/// Whether an escape sequence stands for a line break, which `fix` only resolves on request
+pub fn is_line_break(sequence: &str) -> bool {
+    sequence.eq_ignore_ascii_case("\\n")
+}
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+	match c {
+	    '\n'             => result.push_str("\\n"),
+	    '\\' | ',' | ';' => { result.push('\\'); result.push(c); },
+	    '\r'             => {},
+	    _                => result.push(c),
+	}
+    }
+    result
+}
+
+/// Resolve escape sequences once; other backslashes are kept, and values without escape sequences
+/// are returned unchanged
+pub fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(i) = rest.find('\\') {
+	result.push_str(&rest[..i]);
+	rest = &rest[i..];
+	match SEQUENCES.iter().find(|(sequence, _)| rest.starts_with(sequence)) {
+	    Some((sequence, c)) => {
+		result.push(*c);
+		rest = &rest[sequence.len()..];
+	    },
+	    None => {
+		result.push('\\');
+		rest = &rest[1..];
+	    },
+	}
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Resolve the escape sequences of a text that looks escaped twice, once
+fn resolve(text: &str, newlines: bool) -> Option<String> {
+    literal_escape(text)?;
+    let resolved: String = tokens(text)?.into_iter()
+	.map(|token| match token {
+	    Ok((sequence, _)) if is_line_break(sequence) && !newlines => sequence.to_string(),
+	    Ok((_, c))  => c.to_string(),
+	    Err(text)   => text.to_string(),
+	})
+	.collect();
+    Some(resolved).filter(|resolved| resolved != text)
+}
+
+/// The property with escape sequences in its text resolved, if it is a TEXT property that looks
+/// escaped twice (or more often); "\n" stays as it is unless `newlines` is set.  All layers are
+/// resolved at once, so that running this again changes nothing.
+pub fn fix(property: &Property, newlines: bool) -> Option<Property> {
+    let mut fixed = resolve(&text(property)?, newlines)?;
+    while let Some(resolved) = resolve(&fixed, newlines) {
+	fixed = resolved;
+    }
+    let mut result = text_property(property.key(), &fixed);
+    for (key, parameter) in property.params() {
+	result.add_parameter(key, parameter.value());
+    }
+    Some(result)
+}
+
+/// Fix the calendar's own properties, such as its name and description
+pub fn fix_calendar(calendar: &mut Calendar, newlines: bool) {
+    for property in calendar.properties.iter_mut() {
+	if let Some(fixed) = fix(property, newlines) {
+	    *property = fixed;
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(key: &str, value: &str, newlines: bool) -> Option<String> {
+	fix(&Property::new(key, value), newlines).map(|property| property.value().to_string())
+    }
+
+    #[test]
+    fn finds_literal_escapes_only_in_values_escaped_throughout() {
+	assert_eq!(literal_escape("Lunch\\, then coffee"), Some("\\,"));
+	assert_eq!(literal_escape("Line 1\\nLine 2"), Some("\\n"));
+	assert_eq!(literal_escape("Plain text"), None);
+	// A bare comma or semicolon shows that the value is escaped correctly
+	assert_eq!(literal_escape("C:\\new, folder"), None);
+	assert_eq!(literal_escape("a\\, b; c"), None);
+	// Backslashes that start no escape sequence
+	assert_eq!(literal_escape("\\section{Intro}\\n"), None);
+    }
+
+    #[test]
+    fn resolves_escaped_commas_and_semicolons() {
+	assert_eq!(fixed("SUMMARY", "Lunch\\, then coffee", false), Some("Lunch, then coffee".to_string()));
+	assert_eq!(fixed("LOCATION", "Room 1\\; Building C", false), Some("Room 1; Building C".to_string()));
+	assert_eq!(fixed("SUMMARY", "Lunch, then coffee", false), None);
+    }
+
+    #[test]
+    fn resolves_line_breaks_only_on_request() {
+	assert_eq!(fixed("DESCRIPTION", "Line 1\\nLine 2", false), None);
+	assert_eq!(fixed("DESCRIPTION", "Line 1\\nLine 2", true), Some("Line 1\nLine 2".to_string()));
+	assert_eq!(fixed("DESCRIPTION", "Line 1\\nLine 2\\, more", false), Some("Line 1\\nLine 2, more".to_string()));
+    }
+
+    #[test]
+    fn leaves_paths_and_markup_alone() {
+	assert_eq!(fixed("LOCATION", "C:\\new, folder", true), None);
+	assert_eq!(fixed("DESCRIPTION", "see \\section{Intro}", true), None);
+    }
+
+    #[test]
+    fn resolves_all_layers_at_once() {
+	let once = fixed("SUMMARY", "a\\\\\\, b", false).unwrap();
+	assert_eq!(once, "a, b");
+	assert_eq!(fixed("SUMMARY", &once, false), None);
+    }
+
+    #[test]
+    fn escapes_untyped_text_properties() {
+	let property = text_property("X-WR-CALNAME", "Team, Berlin");
+	assert_eq!(property.value(), "Team\\, Berlin");
+	assert_eq!(text(&property).as_deref(), Some("Team, Berlin"));
+	assert_eq!(fixed("X-WR-CALNAME", "Team\\\\, Berlin", false), Some("Team\\, Berlin".to_string()));
+	assert_eq!(text(&text_property("NAME", "a\\b")).as_deref(), Some("a\\b"));
+    }
+
+    #[test]
+    fn ignores_properties_that_are_not_text() {
+	assert_eq!(text(&Property::new("DTSTART", "20250101T100000Z")), None);
+	assert_eq!(fixed("DTSTART", "2025\\,01", true), None);
+    }
+}
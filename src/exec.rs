@@ -0,0 +1,64 @@
+// Property values mapped through external commands, in batches: the command reads one value per
+// line and writes one result per line, in the same order, e.g. `sed`, `tr` or a script
+
+use std::{collections::{HashMap, HashSet}, io::Write, process::{Command, Stdio}};
+
+/// One value per line; backslashes and line breaks are escaped as in iCalendar text
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+	match c {
+	    '\\' => match chars.next() {
+		Some('n' | 'N') => result.push('\n'),
+		Some(other)     => result.push(other),
+		None            => result.push('\\'),
+	    },
+	    _ => result.push(c),
+	}
+    }
+    result
+}
+
+/// Results of one run of the command over some values
+fn run(command: &[String], values: &[&str]) -> Result<Vec<String>, String> {
+    let mut child = Command::new(&command[0])
+	.args(&command[1..])
+	.stdin(Stdio::piped())
+	.stdout(Stdio::piped())
+	.spawn()
+	.map_err(|e| format!("cannot run '{}': {}", command[0], e))?;
+    let input: String = values.iter().map(|value| escape(value) + "\n").collect();
+    let mut stdin = child.stdin.take().unwrap();
+    // Write from another thread, so that a command that writes before reading everything cannot
+    // block on a full pipe
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    // A command may well stop reading early, e.g. when it fails
+    let _ = writer.join();
+    if !output.status.success() {
+	return Err(format!("'{}' failed ({})", command.join(" "), output.status));
+    }
+    let results: Vec<String> = String::from_utf8_lossy(&output.stdout).lines().map(unescape).collect();
+    match results.len() == values.len() {
+	true  => Ok(results),
+	false => Err(format!("'{}' printed {} lines for {} values", command.join(" "), results.len(), values.len())),
+    }
+}
+
+/// The result for each distinct value, running the command once per `batch` values
+pub fn map_values<'a>(command: &[String], values: impl IntoIterator<Item = &'a str>, batch: usize) -> Result<HashMap<String, String>, String> {
+    let mut seen = HashSet::new();
+    let distinct: Vec<&str> = values.into_iter().filter(|value| seen.insert(*value)).collect();
+    let mut result = HashMap::new();
+    for chunk in distinct.chunks(batch.max(1)) {
+	for (value, mapped) in chunk.iter().zip(run(command, chunk)?) {
+	    result.insert(value.to_string(), mapped);
+	}
+    }
+    Ok(result)
+}
@@ -0,0 +1,83 @@
+// Weekly availability grids: one row per time of day and one column per weekday, marking the
+// cells that are busy, overlaid across several weeks to show regular free slots
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
+
+use crate::locale::Locale;
+use crate::slots::{self, Person};
+
+pub struct Grid {
+    /// Column order
+    pub days: Vec<Weekday>,
+    /// Start of each row
+    pub rows: Vec<NaiveTime>,
+    pub weeks: u32,
+    /// Number of weeks in which each cell is busy, by row, then column
+    busy: Vec<Vec<u32>>,
+}
+
+/// Grid of the `weeks` weeks starting on `first`, with rows of length `step` from `day_start` to
+/// `day_end` (local time); a cell is busy if any busy time overlaps it
+pub fn week(person: &Person, first: NaiveDate, weeks: u32, days: Vec<Weekday>, (day_start, day_end): (NaiveTime, NaiveTime), step: Duration) -> Grid {
+    let rows: Vec<NaiveTime> = std::iter::successors(Some(day_start), |time| Some(*time + step).filter(|next| next > time))
+	.take_while(|time| *time < day_end)
+	.collect();
+    let mut busy = vec![vec![0; days.len()]; rows.len()];
+    for day in first.iter_days().take(7 * weeks as usize) {
+	let Some(column) = days.iter().position(|d| *d == day.weekday()) else {
+	    continue;
+	};
+	for (row, time) in rows.iter().enumerate() {
+	    // The last row may be cut short by the end of the day
+	    let end = (*time + step).min(day_end);
+	    let (Some(start), Some(end)) = (slots::local_time(day, *time), slots::local_time(day, end)) else {
+		continue;
+	    };
+	    if !person.is_free(start, end) {
+		busy[row][column] += 1;
+	    }
+	}
+    }
+    Grid { days, rows, weeks, busy }
+}
+
+impl Grid {
+    /// Plain text: '#' for cells busy in all weeks, '+' for cells busy in some, '.' for free ones
+    pub fn text(&self, locale: &Locale) -> String {
+	let mut result = format!("{:5}", "");
+	for day in &self.days {
+	    result += &format!(" {:>4}", locale.weekday_abbreviation(*day));
+	}
+	result.push('\n');
+	for (time, counts) in self.rows.iter().zip(&self.busy) {
+	    result += &time.format("%H:%M").to_string();
+	    for count in counts {
+		let mark = match *count {
+		    0                     => '.',
+		    n if n >= self.weeks  => '#',
+		    _                     => '+',
+		};
+		result += &format!(" {:>4}", mark);
+	    }
+	    result.push('\n');
+	}
+	result
+    }
+
+    /// CSV with a header row of weekdays; cells give the number of weeks in which they are busy
+    pub fn csv(&self, locale: &Locale) -> String {
+	let mut result = "time".to_string();
+	for day in &self.days {
+	    result += &format!(",{}", locale.weekday_abbreviation(*day));
+	}
+	result.push('\n');
+	for (time, counts) in self.rows.iter().zip(&self.busy) {
+	    result += &time.format("%H:%M").to_string();
+	    for count in counts {
+		result += &format!(",{}", count);
+	    }
+	    result.push('\n');
+	}
+	result
+    }
+}
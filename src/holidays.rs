@@ -0,0 +1,57 @@
+// Public holidays, from a calendar whose all-day events mark days off, e.g. a published holiday
+// feed.  Holidays are whole local days without working time.
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use icalendar::{Calendar, Component};
+use std::collections::BTreeSet;
+
+use crate::recurrence;
+
+/// Maximal number of occurrences of a single (yearly) holiday to consider
+const MAX_OCCURRENCES: u16 = 1000;
+
+#[derive(Default)]
+pub struct Holidays {
+    days: BTreeSet<NaiveDate>,
+}
+
+impl Holidays {
+    /// Days of all-day events (except cancelled ones, and occurrences that overrides replace)
+    /// between `from` and `until`; timed events are ignored
+    pub fn new(calendar: &Calendar, from: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+	let mut days = BTreeSet::new();
+	// Occurrences that overrides move elsewhere (or cancel)
+	let overridden = recurrence::overridden(calendar.components.iter().filter_map(|c| c.as_event()));
+	for event in calendar.components.iter().filter_map(|c| c.as_event()) {
+	    if event.property_value("STATUS").is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED")) {
+		continue;
+	    }
+	    for occurrence in recurrence::occurrences(event, from - Duration::days(1), Some(until), MAX_OCCURRENCES) {
+		if occurrence.all_day && !recurrence::is_overridden(&overridden, event, &occurrence) {
+		    let (first, end) = (occurrence.start.date_naive(), occurrence.end().date_naive());
+		    days.extend(first.iter_days().take_while(|day| *day == first || *day < end));
+		}
+	    }
+	}
+	Holidays { days }
+    }
+
+    pub fn contains(&self, day: NaiveDate) -> bool {
+	self.days.contains(&day)
+    }
+
+    /// Time between `from` and `to` that falls on holidays
+    pub fn time_within(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Duration {
+	let midnight = |day: NaiveDate| Local.from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap()).earliest().map(|t| t.with_timezone(&Utc));
+	let mut result = Duration::zero();
+	for &day in &self.days {
+	    let (Some(start), Some(end)) = (midnight(day), day.succ_opt().and_then(midnight)) else {
+		continue;
+	    };
+	    if start.max(from) < end.min(to) {
+		result += end.min(to) - start.max(from);
+	    }
+	}
+	result
+    }
+}
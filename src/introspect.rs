@@ -0,0 +1,96 @@
+// The command-line interface as data, for front-ends and wrapper scripts: commands, their options
+// and positional arguments, and the syntax of the values that these take
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::{Arg, ArgAction, Command};
+use serde_json::{json, Value};
+use std::any::TypeId;
+
+/// Syntax of the kinds of values (other than plain text, numbers and choices) that arguments take
+pub fn value_syntax() -> Value {
+    json!({
+	"duration": "number and unit (d, h, m, s, ms), repeated, e.g. '1h30m' or '500ms'; or ISO 8601, e.g. 'PT1H30M'; negative with a leading '-'",
+	"date-time": "RFC 3339 (e.g. '2025-04-01T10:00:00+02:00'), iCalendar UTC (e.g. '20250401T080000Z'), or a wall-clock time, in local time",
+	"wall-clock": "date and time without time zone, e.g. '2025-04-01 10:00', '2025-04-01T10:00:00' or '20250401T100000'; dates alone mean midnight",
+    })
+}
+
+/// Kind of value of an argument: "flag", "choice", one of those in `value_syntax`, "integer",
+/// "number" or "text"
+fn value_type(arg: &Arg) -> &'static str {
+    if matches!(arg.get_action(), ArgAction::SetTrue | ArgAction::SetFalse | ArgAction::Count) {
+	return "flag";
+    }
+    if !arg.get_possible_values().is_empty() {
+	return "choice";
+    }
+    let type_id = arg.get_value_parser().type_id();
+    let is = |other: TypeId| type_id == other;
+    if is(TypeId::of::<chrono::Duration>()) {
+	"duration"
+    } else if is(TypeId::of::<DateTime<Utc>>()) {
+	"date-time"
+    } else if is(TypeId::of::<NaiveDateTime>()) {
+	"wall-clock"
+    } else if [TypeId::of::<usize>(), TypeId::of::<u32>(), TypeId::of::<u64>(), TypeId::of::<i32>(), TypeId::of::<i64>()].into_iter().any(is) {
+	"integer"
+    } else if is(TypeId::of::<f64>()) {
+	"number"
+    } else {
+	"text"
+    }
+}
+
+fn argument(arg: &Arg) -> Value {
+    let mut result = json!({
+	"name": arg.get_id().as_str(),
+	"type": value_type(arg),
+	"help": arg.get_long_help().or(arg.get_help()).map(|help| help.to_string()),
+	"required": arg.is_required_set(),
+	"multiple": matches!(arg.get_action(), ArgAction::Append) || arg.get_num_args().is_some_and(|n| n.max_values() > 1),
+    });
+    let object = result.as_object_mut().unwrap();
+    if let Some(long) = arg.get_long() {
+	object.insert("long".to_string(), json!(format!("--{}", long)));
+    }
+    if let Some(short) = arg.get_short() {
+	object.insert("short".to_string(), json!(format!("-{}", short)));
+    }
+    if let Some(delimiter) = arg.get_value_delimiter() {
+	object.insert("delimiter".to_string(), json!(delimiter.to_string()));
+    }
+    let defaults: Vec<String> = arg.get_default_values().iter().map(|value| value.to_string_lossy().to_string()).collect();
+    if !defaults.is_empty() && value_type(arg) != "flag" {
+	object.insert("default".to_string(), json!(defaults.join(",")));
+    }
+    let choices: Vec<Value> = arg.get_possible_values().iter()
+	.filter(|value| !value.is_hide_set())
+	.map(|value| json!({"name": value.get_name(), "help": value.get_help().map(|help| help.to_string())}))
+	.collect();
+    if !choices.is_empty() {
+	object.insert("choices".to_string(), json!(choices));
+    }
+    result
+}
+
+/// A command with its arguments and subcommands
+pub fn command(command: &Command) -> Value {
+    let arguments = || command.get_arguments().filter(|arg| !arg.is_hide_set());
+    json!({
+	"name": command.get_name(),
+	"about": command.get_long_about().or(command.get_about()).map(|about| about.to_string()),
+	"aliases": command.get_visible_aliases().collect::<Vec<_>>(),
+	"options": arguments().filter(|arg| !arg.is_positional()).map(argument).collect::<Vec<_>>(),
+	"positionals": arguments().filter(|arg| arg.is_positional()).map(argument).collect::<Vec<_>>(),
+	"commands": command.get_subcommands().filter(|sub| !sub.is_hide_set()).map(self::command).collect::<Vec<_>>(),
+    })
+}
+
+/// One line per command, "path  about", indented by depth
+pub fn outline(command: &Command, depth: usize, result: &mut Vec<String>) {
+    for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+	let about = sub.get_about().map_or(String::new(), |about| about.to_string());
+	result.push(format!("{:indent$}{:<width$}  {}", "", sub.get_name(), about, indent = 2 * depth, width = 20usize.saturating_sub(2 * depth)));
+	outline(sub, depth + 1, result);
+    }
+}
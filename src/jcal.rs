@@ -0,0 +1,208 @@
+// jCal (RFC 7265), the JSON form of iCalendar: ["vcalendar", [properties], [components]], where
+// each property is [name, {parameters}, type, value, ...].  Converted to iCalendar text, so that
+// everything downstream only deals with one format.
+
+use serde_json::{Map, Value};
+
+/// Whether a JSON document looks like jCal: a "vcalendar" component, or an array of them
+pub fn is_jcal(document: &Value) -> bool {
+    match document {
+	Value::Array(items) => match items.first() {
+	    Some(Value::String(name)) => name.eq_ignore_ascii_case("vcalendar"),
+	    Some(first @ Value::Array(_)) => is_jcal(first),
+	    _ => false,
+	},
+	_ => false,
+    }
+}
+
+/// iCalendar text of a jCal document
+pub fn to_ics(document: &Value) -> Result<String, String> {
+    let mut result = String::new();
+    match document.as_array().and_then(|items| items.first()) {
+	Some(Value::Array(_)) => for calendar in document.as_array().unwrap() {
+	    component(calendar, &mut result)?;
+	},
+	_ => component(document, &mut result)?,
+    }
+    Ok(result)
+}
+
+fn component(value: &Value, result: &mut String) -> Result<(), String> {
+    let (Some(name), Some(properties), components) = (
+	value.get(0).and_then(Value::as_str),
+	value.get(1).and_then(Value::as_array),
+	value.get(2).and_then(Value::as_array),
+    ) else {
+	return Err(format!("expected [name, [properties], [components]], not {}", value));
+    };
+    let name = name.to_uppercase();
+    result.push_str(&format!("BEGIN:{}\r\n", name));
+    for property in properties {
+	result.push_str(&content_line(property)?);
+	result.push_str("\r\n");
+    }
+    for child in components.into_iter().flatten() {
+	component(child, result)?;
+    }
+    result.push_str(&format!("END:{}\r\n", name));
+    Ok(())
+}
+
+/// One (unfolded) content line from [name, {parameters}, type, value, ...]
+fn content_line(property: &Value) -> Result<String, String> {
+    let invalid = || format!("expected [name, {{parameters}}, type, value...], not {}", property);
+    let items = property.as_array().ok_or_else(invalid)?;
+    let (Some(name), Some(params), Some(value_type)) = (
+	items.first().and_then(Value::as_str),
+	items.get(1).and_then(Value::as_object),
+	items.get(2).and_then(Value::as_str),
+    ) else {
+	return Err(invalid());
+    };
+    let name = name.to_uppercase();
+    let mut line = name.clone();
+    for (key, value) in params {
+	line += &format!(";{}={}", key.to_uppercase(), parameter(value));
+    }
+    // DATE and PERIOD values are never the default type of the properties that allow them
+    if matches!(value_type, "date" | "period") && !params.keys().any(|key| key.eq_ignore_ascii_case("value")) {
+	line += &format!(";VALUE={}", value_type.to_uppercase());
+    }
+    let values: Vec<String> = items[3..].iter().map(|value| self::value(value_type, value)).collect();
+    // Structured values (GEO, REQUEST-STATUS) are separated by semicolons, multiple ones by commas
+    let separator = if matches!(name.as_str(), "GEO" | "REQUEST-STATUS") { ";" } else { "," };
+    line.push(':');
+    line += &values.join(separator);
+    Ok(line)
+}
+
+fn parameter(value: &Value) -> String {
+    let quoted = |text: &str| match text.contains([':', ';', ',']) {
+	true  => format!("\"{}\"", text),
+	false => text.to_string(),
+    };
+    match value {
+	Value::Array(items) => items.iter().map(|item| quoted(&scalar(item))).collect::<Vec<_>>().join(","),
+	_                   => quoted(&scalar(value)),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+	Value::String(text) => text.clone(),
+	Value::Bool(true)   => "TRUE".to_string(),
+	Value::Bool(false)  => "FALSE".to_string(),
+	_                   => value.to_string(),
+    }
+}
+
+/// A value in iCalendar syntax
+fn value(value_type: &str, value: &Value) -> String {
+    match (value_type, value) {
+	("text", Value::String(text)) => {
+	    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+	},
+	("date" | "date-time" | "period", Value::String(text)) => text.split('/')
+	    .map(|part| match part.starts_with('P') || part.starts_with("-P") || part.starts_with("+P") {
+		true  => part.to_string(),
+		false => part.replace(['-', ':'], ""),
+	    })
+	    .collect::<Vec<_>>()
+	    .join("/"),
+	// RFC 7265 writes periods as [start, end or duration]
+	("period", Value::Array(items)) => self::value("period", &Value::String(items.iter().map(scalar).collect::<Vec<_>>().join("/"))),
+	("time", Value::String(text)) | ("utc-offset", Value::String(text)) => text.replace(':', ""),
+	("recur", Value::Object(parts)) => recur(parts),
+	// Structured values, e.g. REQUEST-STATUS
+	(_, Value::Array(items)) => items.iter().map(scalar).collect::<Vec<_>>().join(";"),
+	_ => scalar(value),
+    }
+}
+
+/// RRULE parts: {"freq": "WEEKLY", "byday": ["MO", "WE"], "until": "2025-06-30T00:00:00Z"}
+fn recur(parts: &Map<String, Value>) -> String {
+    let part = |key: &str, value: &Value| {
+	let text = match value {
+	    Value::Array(items) => items.iter().map(scalar).collect::<Vec<_>>().join(","),
+	    _                   => scalar(value),
+	};
+	match key {
+	    "until" => text.replace(['-', ':'], ""),
+	    _       => text,
+	}
+    };
+    // FREQ goes first, as RFC 2445 required
+    let mut parts: Vec<(&String, &Value)> = parts.iter().collect();
+    parts.sort_by_key(|(key, _)| !key.eq_ignore_ascii_case("freq"));
+    parts.into_iter()
+	.map(|(key, value)| format!("{}={}", key.to_uppercase(), part(key, value)))
+	.collect::<Vec<_>>()
+	.join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn calendar(properties: Value) -> Value {
+	json!(["vcalendar", [["version", {}, "text", "2.0"]], [["vevent", properties, []]]])
+    }
+
+    #[test]
+    fn recognises_jcal() {
+	assert!(is_jcal(&calendar(json!([]))));
+	assert!(is_jcal(&json!([calendar(json!([]))])));
+	assert!(!is_jcal(&json!({"vcalendar": []})));
+	assert!(!is_jcal(&json!(["vcard", [], []])));
+    }
+
+    #[test]
+    fn converts_components_and_text() {
+	let ics = to_ics(&calendar(json!([["summary", {"language": "en"}, "text", "Lunch; then, coffee"]]))).unwrap();
+	assert_eq!(ics, "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nSUMMARY;LANGUAGE=en:Lunch\\; then\\, coffee\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n");
+    }
+
+    #[test]
+    fn converts_date_times_and_dates() {
+	let ics = to_ics(&calendar(json!([
+	    ["dtstart", {"tzid": "Europe/Berlin"}, "date-time", "2025-03-03T10:00:00"],
+	    ["dtend", {}, "date", "2025-03-04"],
+	    ["exdate", {}, "date-time", "2025-03-10T09:00:00Z", "2025-03-17T09:00:00Z"],
+	]))).unwrap();
+	assert!(ics.contains("DTSTART;TZID=Europe/Berlin:20250303T100000\r\n"), "{}", ics);
+	assert!(ics.contains("DTEND;VALUE=DATE:20250304\r\n"), "{}", ics);
+	assert!(ics.contains("EXDATE:20250310T090000Z,20250317T090000Z\r\n"), "{}", ics);
+    }
+
+    #[test]
+    fn converts_rrule() {
+	let ics = to_ics(&calendar(json!([
+	    ["rrule", {}, "recur", {"byday": ["MO", "WE"], "until": "2025-06-30T00:00:00Z", "freq": "WEEKLY"}],
+	]))).unwrap();
+	assert!(ics.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20250630T000000Z\r\n"), "{}", ics);
+    }
+
+    #[test]
+    fn converts_periods() {
+	let ics = to_ics(&calendar(json!([
+	    ["rdate", {}, "period", ["2025-03-08T16:00:00Z", "PT8H30M"]],
+	    ["rdate", {}, "period", "2025-03-09T16:00:00Z/2025-03-09T18:00:00Z"],
+	]))).unwrap();
+	assert!(ics.contains("RDATE;VALUE=PERIOD:20250308T160000Z/PT8H30M\r\n"), "{}", ics);
+	assert!(ics.contains("RDATE;VALUE=PERIOD:20250309T160000Z/20250309T180000Z\r\n"), "{}", ics);
+    }
+
+    #[test]
+    fn converts_geo() {
+	let ics = to_ics(&calendar(json!([["geo", {}, "float", [37.386013, -122.082932]]]))).unwrap();
+	assert!(ics.contains("GEO:37.386013;-122.082932\r\n"), "{}", ics);
+    }
+
+    #[test]
+    fn rejects_malformed_properties() {
+	assert!(to_ics(&calendar(json!([["summary", "not parameters", "text", "x"]]))).is_err());
+	assert!(to_ics(&json!(["vcalendar"])).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+// Events from JSON or YAML documents, e.g. as generated by scripts
+
+use chrono::{DateTime, Utc};
+use icalendar::{Component, Event, Property};
+use serde_json::Value;
+
+use crate::csv_import;
+
+/// Text of a scalar property value; None for null, objects and arrays
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+	Value::String(s) => Some(s.clone()),
+	Value::Number(n) => Some(n.to_string()),
+	Value::Bool(b)   => Some(b.to_string().to_uppercase()),
+	_                => None,
+    }
+}
+
+/// DATE or DATE-TIME property from ISO 8601 / RFC 3339 or iCalendar-style values; values with an
+/// offset or "Z" are converted to UTC
+fn date_time_property(key: &str, value: &str, tzid: Option<&str>) -> Option<Property> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+	return Some(Property::new(key, date_time.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()));
+    }
+    if let Some(local) = value.strip_suffix('Z') {
+	let property = csv_import::date_time_property(key, local, None, None)?;
+	return Some(Property::new(key, format!("{}Z", property.value())));
+    }
+    csv_import::date_time_property(key, value, None, tzid)
+}
+
+/// Property from a string (or number, or boolean), or from {"value": ..., "params": {...}}
+fn property(key: &str, value: &Value) -> Result<Property, String> {
+    let (text, params) = match value {
+	Value::Object(fields) => {
+	    let text = fields.get("value").and_then(scalar)
+		.ok_or_else(|| format!("{}: object without a \"value\"", key))?;
+	    let mut params = vec![];
+	    if let Some(given) = fields.get("params") {
+		let Value::Object(given) = given else {
+		    return Err(format!("{}: \"params\" must be an object", key));
+		};
+		for (name, value) in given {
+		    let value = scalar(value).ok_or_else(|| format!("{}: parameter {} must be a string", key, name))?;
+		    params.push((name.to_uppercase(), value));
+		}
+	    }
+	    (text, params)
+	},
+	_ => (scalar(value).ok_or_else(|| format!("{}: unsupported value {}", key, value))?, vec![]),
+    };
+
+    let is_date_time = csv_import::DATE_TIME_PROPERTIES.contains(&key);
+    let mut property = if is_date_time {
+	let tzid = params.iter().find(|(name, _)| name == "TZID").map(|(_, value)| value.as_str());
+	date_time_property(key, &text, tzid)
+	    .ok_or_else(|| format!("{}: cannot read '{}' as a date or date-time", key, text))?
+    } else {
+	Property::new(key, &text)
+    };
+    // The TZID of date-times only applies to local times, and was handled above
+    for (name, value) in params.iter().filter(|(name, _)| name != "TZID" || !is_date_time) {
+	property.add_parameter(name, value);
+    }
+    Ok(property)
+}
+
+/// Event from an object mapping (case-insensitive) property names to values; arrays give
+/// repeated properties such as ATTENDEE
+fn event(object: &serde_json::Map<String, Value>, index: usize) -> Result<Event, String> {
+    let mut event = Event::new();
+    for (key, value) in object {
+	let key = key.to_uppercase();
+	match value {
+	    Value::Null         => {},
+	    Value::Array(items) => for item in items {
+		event.append_multi_property(property(&key, item)?);
+	    },
+	    _                   => { event.append_property(property(&key, value)?); },
+	}
+    }
+    if !event.properties().contains_key("DTSTART") {
+	return Err("no DTSTART".to_string());
+    }
+    if !event.properties().contains_key("UID") {
+	let uid = format!("{:016x}-{}@icalm", crate::fnv1a(&Value::Object(object.clone()).to_string()), index);
+	event.add_property("UID", &uid);
+    }
+    event.timestamp(Utc::now());
+    Ok(event)
+}
+
+/// Events for an array of event objects (or a single one); unusable entries are reported and skipped
+pub fn import(document: &Value) -> Result<Vec<Event>, String> {
+    let entries = match document {
+	Value::Array(entries) => entries.iter().collect(),
+	Value::Object(_)      => vec![document],
+	_                     => return Err("expected an array of event objects".to_string()),
+    };
+    let mut events = vec![];
+    for (index, entry) in entries.into_iter().enumerate() {
+	let Value::Object(object) = entry else {
+	    crate::warn(format!("Entry {}: not an object; skipping", index + 1));
+	    continue;
+	};
+	match event(object, index) {
+	    Ok(event) => events.push(event),
+	    Err(e)    => crate::warn(format!("Entry {}: {}; skipping", index + 1, e)),
+	}
+    }
+    Ok(events)
+}
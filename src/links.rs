@@ -0,0 +1,109 @@
+// Detection of URLs, in particular of video-conference links, in event properties
+
+use icalendar::Component;
+
+/// Hosts of video-conference services, by provider; subdomains match as well
+const CONFERENCE_HOSTS: &[(&str, &str)] = &[
+    ("zoom.us", "Zoom"),
+    ("zoomgov.com", "Zoom"),
+    ("meet.google.com", "Google Meet"),
+    ("teams.microsoft.com", "Microsoft Teams"),
+    ("teams.live.com", "Microsoft Teams"),
+    ("meet.jit.si", "Jitsi"),
+    ("8x8.vc", "Jitsi"),
+];
+
+/// Properties that may carry conference links, most authoritative first
+const LINK_PROPERTIES: &[&str] = &["CONFERENCE", "URL", "LOCATION", "DESCRIPTION"];
+
+pub struct MeetingLink {
+    pub provider: &'static str,
+    pub url: String,
+}
+
+/// http(s) URLs in a text, with their byte offsets
+pub fn find_urls(text: &str) -> Vec<(usize, &str)> {
+    let mut urls = vec![];
+    let mut offset = 0;
+    while let Some(start) = ["http://", "https://"].iter().filter_map(|scheme| text[offset..].find(scheme)).min() {
+	let start = offset + start;
+	let length = text[start..].find(|c: char| c.is_whitespace() || "<>\"".contains(c)).unwrap_or(text.len() - start);
+	// Trailing punctuation most likely belongs to the sentence
+	let url = text[start..start + length].trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+	urls.push((start, url));
+	offset = start + url.len().max(1);
+    }
+    urls
+}
+
+/// Video-conference provider of a URL, if any
+pub fn conference_provider(url: &str) -> Option<&'static str> {
+    let rest = url.split_once("://")?.1;
+    let host = rest.split(['/', '?', '#']).next()?.split('@').next_back()?.split(':').next()?.to_lowercase();
+    CONFERENCE_HOSTS.iter()
+	.find(|(domain, _)| host == *domain || host.ends_with(&format!(".{}", domain)))
+	.map(|(_, provider)| *provider)
+}
+
+/// All distinct video-conference links of an event
+pub fn meeting_links<C: Component>(event: &C) -> Vec<MeetingLink> {
+    let mut links: Vec<MeetingLink> = vec![];
+    for key in LINK_PROPERTIES {
+	let properties = event.properties().get(*key).into_iter()
+	    .chain(event.multi_properties().get(*key).into_iter().flatten());
+	for property in properties {
+	    for (_, url) in find_urls(property.value()) {
+		if let Some(provider) = conference_provider(url) {
+		    if !links.iter().any(|link| link.url == url) {
+			links.push(MeetingLink { provider, url: url.to_string() });
+		    }
+		}
+	    }
+	}
+    }
+    links
+}
+
+/// Typical lines of the join instructions that conferencing services add to descriptions
+const JOIN_INSTRUCTION_PREFIXES: &[&str] = &[
+    "join zoom meeting", "meeting id:", "passcode:", "password:", "one tap mobile", "dial by your location",
+    "find your local number", "join by phone", "join with google meet", "more phone numbers",
+    "microsoft teams meeting", "join on your computer", "click here to join", "join the meeting now",
+    "meeting options", "learn more", "join by video system", "join from a video conferencing device",
+];
+
+/// Lines such as "_____" or "──────" that conferencing services use to set off join instructions
+fn is_separator(line: &str) -> bool {
+    let line = line.trim();
+    line.chars().count() >= 10 && line.chars().all(|c| "_-=─━".contains(c))
+}
+
+/// Remove the join instructions around conference links from a description: the block between
+/// two separator lines that contains a link, if any, and otherwise all lines that contain a link or
+/// start with typical instruction phrases
+pub fn strip_join_instructions(description: &str) -> String {
+    let lines: Vec<&str> = description.lines().collect();
+    let has_link = |line: &&str| find_urls(line).iter().any(|(_, url)| conference_provider(url).is_some());
+    let separators: Vec<usize> = (0..lines.len()).filter(|&i| is_separator(lines[i])).collect();
+
+    // Separators on both sides of the first link; one on a single side may as well set off the
+    // agenda from the instructions
+    let block = lines.iter().position(has_link).and_then(|link| Some((
+	separators.iter().rev().find(|&&s| s < link).copied()?,
+	separators.iter().find(|&&s| s > link).copied()?,
+    )));
+
+    let mut keep = vec![true; lines.len()];
+    if let Some((start, end)) = block {
+	keep[start..=end].iter_mut().for_each(|k| *k = false);
+    } else {
+	for (i, line) in lines.iter().enumerate() {
+	    let lower = line.trim().to_lowercase();
+	    if has_link(line) || JOIN_INSTRUCTION_PREFIXES.iter().any(|prefix| lower.starts_with(prefix)) {
+		keep[i] = false;
+	    }
+	}
+    }
+    let kept: Vec<&str> = lines.iter().zip(keep).filter(|(_, k)| *k).map(|(line, _)| *line).collect();
+    kept.join("\n").trim().to_string()
+}
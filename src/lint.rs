@@ -0,0 +1,218 @@
+// Consistency checks for calendars
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use icalendar::{Calendar, CalendarComponent, Component, Property, ValueType};
+
+use crate::{escaping, recurrence, tz};
+
+/// Maximal number of disagreeing date ranges to report per VTIMEZONE
+const MAX_TZ_MISMATCHES: usize = 3;
+
+/// Check the calendar and describe all problems found
+pub fn lint(calendar: &Calendar) -> Vec<String> {
+    let mut warnings = vec![];
+    lint_timezones(calendar, &mut warnings);
+    for group in tz::equivalent_timezones(calendar) {
+	warnings.push(format!("VTIMEZONEs {} are equivalent; `icalm tz-merge` unifies them",
+			      group.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", ")));
+    }
+    for property in &calendar.properties {
+	lint_escaping(property, "VCALENDAR", &mut warnings);
+    }
+    for component in &calendar.components {
+	match component {
+	    CalendarComponent::Event(event) => lint_values(event, "", &mut warnings),
+	    CalendarComponent::Todo(todo)   => lint_values(todo, "", &mut warnings),
+	    CalendarComponent::Venue(venue) => lint_values(venue, "", &mut warnings),
+	    CalendarComponent::Other(other) => lint_values(other, "", &mut warnings),
+	    _ => {},
+	}
+    }
+    warnings
+}
+
+// --------------------------------------------------------------------------------
+
+/// Human-readable name of a component for warnings, e.g. "VEVENT 1234@example.com > VALARM"
+fn describe_component<C: Component>(component: &C, parent: &str) -> String {
+    let kind = component.component_kind();
+    let name = match component.property_value("UID").or(component.property_value("TZID")) {
+	Some(id) => format!("{} {}", kind, id),
+	None     => kind,
+    };
+    if parent.is_empty() { name } else { format!("{} > {}", parent, name) }
+}
+
+/// Check that all property values of a component and its sub-components conform to their
+/// declared (VALUE=...) or default value type
+fn lint_values<C: Component>(component: &C, parent: &str, warnings: &mut Vec<String>) {
+    let name = describe_component(component, parent);
+    for property in component.properties().values().chain(component.multi_properties().values().flatten()) {
+	let Some(value_type) = property.value_type() else {
+	    continue;
+	};
+	let problem = if property.key() == "GEO" {
+	    check_geo(property.value())
+	} else if matches!(value_type, ValueType::Date | ValueType::DateTime) {
+	    // EXDATE, RDATE etc. may list several values
+	    property.value().split(',').find_map(|v| check_value(value_type, v))
+	} else {
+	    check_value(value_type, property.value())
+	};
+	if let Some(problem) = problem {
+	    warnings.push(format!("{}: {} value '{}' {}", name, property.key(), property.value(), problem));
+	}
+	lint_escaping(property, &name, warnings);
+    }
+    for conference in component.multi_properties().get("CONFERENCE").into_iter().flatten().chain(component.properties().get("CONFERENCE")) {
+	if !conference.params().get("VALUE").is_some_and(|v| v.value().eq_ignore_ascii_case("URI")) {
+	    warnings.push(format!("{}: CONFERENCE '{}' lacks the required VALUE=URI parameter", name, conference.value()));
+	}
+    }
+    for child in component.components() {
+	lint_values(child, &name, warnings);
+    }
+}
+
+/// Check that a TEXT value was not escaped twice
+fn lint_escaping(property: &Property, name: &str, warnings: &mut Vec<String>) {
+    let Some(text) = escaping::text(property) else {
+	return;
+    };
+    if let Some(sequence) = escaping::literal_escape(&text) {
+	let repair = match escaping::is_line_break(sequence) {
+	    true  => "icalm fix-escaping --newlines",
+	    false => "icalm fix-escaping",
+	};
+	warnings.push(format!("{}: {} value '{}' contains a literal '{}', probably escaped twice; `{}` repairs this",
+			      name, property.key(), property.value(), sequence, repair));
+    }
+}
+
+/// Check one value against a value type; describe the problem, if any
+fn check_value(value_type: ValueType, value: &str) -> Option<String> {
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    match value_type {
+	ValueType::Date => {
+	    if value.contains('T') {
+		Some("is declared as DATE but contains a time".to_string())
+	    } else if value.len() != 8 || !all_digits(value) {
+		Some("is not a DATE (YYYYMMDD)".to_string())
+	    } else if NaiveDate::parse_from_str(value, "%Y%m%d").is_err() {
+		Some("is an impossible date".to_string())
+	    } else { None }
+	},
+	ValueType::DateTime => {
+	    let local = value.strip_suffix('Z').unwrap_or(value);
+	    let well_formed = local.len() == 15 && local.as_bytes()[8] == b'T'
+		&& all_digits(&local[..8]) && all_digits(&local[9..]);
+	    if value.len() == 8 && all_digits(value) {
+		Some("is a DATE, but VALUE=DATE is missing".to_string())
+	    } else if !well_formed {
+		Some("is not a DATE-TIME (YYYYMMDDTHHMMSS[Z])".to_string())
+	    } else if NaiveDateTime::parse_from_str(local, "%Y%m%dT%H%M%S").is_err() {
+		Some("is an impossible date or time".to_string())
+	    } else { None }
+	},
+	ValueType::Duration => match recurrence::parse_duration(value) {
+	    None => Some("is not a valid DURATION (e.g., PT1H30M)".to_string()),
+	    _    => None,
+	},
+	ValueType::UtcOffset => match tz::parse_utc_offset(value) {
+	    None => Some("is not a valid UTC-OFFSET (e.g., +0100)".to_string()),
+	    _    => None,
+	},
+	ValueType::Integer => match value.parse::<i32>() {
+	    Err(_) => Some("is not an INTEGER".to_string()),
+	    _      => None,
+	},
+	ValueType::Float => match value.parse::<f64>() {
+	    Err(_) => Some("is not a FLOAT".to_string()),
+	    _      => None,
+	},
+	ValueType::Boolean => match value.to_uppercase().as_str() {
+	    "TRUE" | "FALSE" => None,
+	    _                => Some("is not a BOOLEAN".to_string()),
+	},
+	_ => None,
+    }
+}
+
+/// GEO is a pair of FLOATs: latitude;longitude
+fn check_geo(value: &str) -> Option<String> {
+    let coordinates: Vec<Option<f64>> = value.split(';').map(|v| v.parse().ok()).collect();
+    match coordinates[..] {
+	[Some(lat), Some(lon)] if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) => None,
+	_ => Some("is not a valid latitude;longitude pair".to_string()),
+    }
+}
+
+/// Years in which events start in the given time zone
+fn years_using_tzid(calendar: &Calendar, tzid: &str) -> Vec<i32> {
+    calendar.components.iter()
+	.filter_map(|c| c.as_event())
+	.filter_map(|event| event.properties().get("DTSTART"))
+	.filter(|dtstart| dtstart.params().get("TZID").is_some_and(|t| t.value() == tzid))
+	.filter_map(recurrence::property_date_time)
+	.map(|start| start.year())
+	.collect()
+}
+
+/// Compare embedded VTIMEZONE definitions of IANA zones against the IANA database, over the years
+/// in which events use them and the near future
+fn lint_timezones(calendar: &Calendar, warnings: &mut Vec<String>) {
+    let this_year = Utc::now().year();
+    for component in &calendar.components {
+	let CalendarComponent::Other(vtimezone) = component else {
+	    continue;
+	};
+	if vtimezone.component_kind() != "VTIMEZONE" {
+	    continue;
+	}
+	let Some(tzid) = vtimezone.property_value("TZID") else {
+	    warnings.push("VTIMEZONE without TZID".to_string());
+	    continue;
+	};
+	// Non-IANA names cannot be checked
+	let Some(iana_tz) = recurrence::resolve_tz(tzid) else {
+	    continue;
+	};
+
+	let years = years_using_tzid(calendar, tzid);
+	let first_year = years.iter().copied().chain([this_year - 1]).min().unwrap().max(1970);
+	let last_year = years.iter().copied().chain([this_year + 2]).max().unwrap().min(2100);
+	let until = Utc.with_ymd_and_hms(last_year + 1, 1, 1, 0, 0, 0).unwrap();
+	let rules = tz::VTimezoneRules::new(vtimezone, until);
+
+	// Sample once a day and collect ranges of days on which the offsets disagree
+	let mut mismatches: Vec<(DateTime<Utc>, DateTime<Utc>, i32, i32)> = vec![];
+	let mut day = Utc.with_ymd_and_hms(first_year, 1, 1, 12, 0, 0).unwrap();
+	while day < until {
+	    let Some(embedded) = rules.offset_at(day) else {
+		warnings.push(format!("VTIMEZONE '{}' has no usable STANDARD/DAYLIGHT definition", tzid));
+		break;
+	    };
+	    let iana = tz::iana_offset(iana_tz, day);
+	    if embedded != iana {
+		match mismatches.last_mut() {
+		    Some((_, last, e, i)) if *last + Duration::days(1) == day && (*e, *i) == (embedded, iana) => *last = day,
+		    _ => mismatches.push((day, day, embedded, iana)),
+		}
+	    }
+	    day += Duration::days(1);
+	}
+
+	for (first, last, embedded, iana) in mismatches.iter().take(MAX_TZ_MISMATCHES) {
+	    warnings.push(format!("VTIMEZONE '{}' disagrees with the IANA database from {} to {}: {} instead of {}",
+				  tzid, first.format("%Y-%m-%d"), last.format("%Y-%m-%d"),
+				  tz::format_utc_offset(*embedded), tz::format_utc_offset(*iana)));
+	}
+	if mismatches.len() > MAX_TZ_MISMATCHES {
+	    warnings.push(format!("VTIMEZONE '{}' disagrees with the IANA database in {} further periods",
+				  tzid, mismatches.len() - MAX_TZ_MISMATCHES));
+	}
+	if !mismatches.is_empty() {
+	    warnings.push(format!("VTIMEZONE '{}' looks stale; `icalm tz-embed --force` replaces it", tzid));
+	}
+    }
+}
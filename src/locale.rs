@@ -0,0 +1,337 @@
+// Locale conventions for human-readable output (digests, schedules, diaries): names of weekdays
+// and months, the few phrases around them, 12- or 24-hour clock, and the first day of the week.
+// Dates themselves stay ISO 8601.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
+
+struct Names {
+    /// Monday first
+    weekdays: [&'static str; 7],
+    abbreviations: [&'static str; 7],
+    months: [&'static str; 12],
+    phrases: Phrases,
+}
+
+/// Words of digests, schedules and `next`
+struct Phrases {
+    all_day: &'static str,
+    no_events: &'static str,
+    no_summary: &'static str,
+    schedule: &'static str,
+    /// Between the first and last date of a range
+    to: &'static str,
+    week_of: &'static str,
+    today: &'static str,
+    tomorrow: &'static str,
+    now: &'static str,
+    until: &'static str,
+    /// Before a time span ahead, as in "in 2 d"
+    ahead: &'static str,
+    /// Units of days, hours and minutes
+    units: [&'static str; 3],
+}
+
+const ENGLISH: Names = Names {
+    weekdays: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+    abbreviations: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    months: ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+    phrases: Phrases {
+	all_day: "all day",
+	no_events: "No events.",
+	no_summary: "(no summary)",
+	schedule: "Schedule",
+	to: "to",
+	week_of: "week of",
+	today: "today",
+	tomorrow: "tomorrow",
+	now: "now",
+	until: "until",
+	ahead: "in",
+	units: ["d", "h", "m"],
+    },
+};
+
+const GERMAN: Names = Names {
+    weekdays: ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"],
+    abbreviations: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    months: ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+    phrases: Phrases {
+	all_day: "ganztägig",
+	no_events: "Keine Termine.",
+	no_summary: "(ohne Titel)",
+	schedule: "Termine",
+	to: "bis",
+	week_of: "Woche vom",
+	today: "heute",
+	tomorrow: "morgen",
+	now: "jetzt",
+	until: "bis",
+	ahead: "in",
+	units: ["T", "Std", "Min"],
+    },
+};
+
+const FRENCH: Names = Names {
+    weekdays: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+    abbreviations: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+    months: ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+    phrases: Phrases {
+	all_day: "toute la journée",
+	no_events: "Aucun événement.",
+	no_summary: "(sans titre)",
+	schedule: "Agenda",
+	to: "au",
+	week_of: "semaine du",
+	today: "aujourd'hui",
+	tomorrow: "demain",
+	now: "maintenant",
+	until: "jusqu'à",
+	ahead: "dans",
+	units: ["j", "h", "min"],
+    },
+};
+
+const SPANISH: Names = Names {
+    weekdays: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+    abbreviations: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    months: ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+    phrases: Phrases {
+	all_day: "todo el día",
+	no_events: "Sin eventos.",
+	no_summary: "(sin título)",
+	schedule: "Agenda",
+	to: "a",
+	week_of: "semana del",
+	today: "hoy",
+	tomorrow: "mañana",
+	now: "ahora",
+	until: "hasta",
+	ahead: "en",
+	units: ["d", "h", "min"],
+    },
+};
+
+const ITALIAN: Names = Names {
+    weekdays: ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"],
+    abbreviations: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+    months: ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+    phrases: Phrases {
+	all_day: "tutto il giorno",
+	no_events: "Nessun evento.",
+	no_summary: "(senza titolo)",
+	schedule: "Agenda",
+	to: "a",
+	week_of: "settimana del",
+	today: "oggi",
+	tomorrow: "domani",
+	now: "ora",
+	until: "fino alle",
+	ahead: "tra",
+	units: ["g", "h", "min"],
+    },
+};
+
+const DUTCH: Names = Names {
+    weekdays: ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"],
+    abbreviations: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+    months: ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus", "september", "oktober", "november", "december"],
+    phrases: Phrases {
+	all_day: "hele dag",
+	no_events: "Geen afspraken.",
+	no_summary: "(geen titel)",
+	schedule: "Agenda",
+	to: "tot",
+	week_of: "week van",
+	today: "vandaag",
+	tomorrow: "morgen",
+	now: "nu",
+	until: "tot",
+	ahead: "over",
+	units: ["d", "u", "min"],
+    },
+};
+
+const PORTUGUESE: Names = Names {
+    weekdays: ["segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado", "domingo"],
+    abbreviations: ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+    months: ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+    phrases: Phrases {
+	all_day: "dia inteiro",
+	no_events: "Nenhum evento.",
+	no_summary: "(sem título)",
+	schedule: "Agenda",
+	to: "a",
+	week_of: "semana de",
+	today: "hoje",
+	tomorrow: "amanhã",
+	now: "agora",
+	until: "até",
+	ahead: "em",
+	units: ["d", "h", "min"],
+    },
+};
+
+const SWEDISH: Names = Names {
+    weekdays: ["måndag", "tisdag", "onsdag", "torsdag", "fredag", "lördag", "söndag"],
+    abbreviations: ["mån", "tis", "ons", "tors", "fre", "lör", "sön"],
+    months: ["januari", "februari", "mars", "april", "maj", "juni", "juli", "augusti", "september", "oktober", "november", "december"],
+    phrases: Phrases {
+	all_day: "heldag",
+	no_events: "Inga händelser.",
+	no_summary: "(ingen titel)",
+	schedule: "Schema",
+	to: "till",
+	week_of: "vecka från",
+	today: "i dag",
+	tomorrow: "i morgon",
+	now: "nu",
+	until: "till",
+	ahead: "om",
+	units: ["d", "tim", "min"],
+    },
+};
+
+#[derive(Clone, Copy)]
+pub struct Locale {
+    names: &'static Names,
+    twelve_hour: bool,
+    week_start: Weekday,
+}
+
+/// Without --locale: English names, 24-hour clock, weeks starting on Monday
+pub const DEFAULT: Locale = Locale { names: &ENGLISH, twelve_hour: false, week_start: Weekday::Mon };
+
+/// Parse a locale such as "de", "en-US" or "pt_BR.UTF-8"
+pub fn parse(tag: &str) -> Result<Locale, String> {
+    let tag = tag.split(['.', '@']).next().unwrap_or("").to_lowercase().replace('_', "-");
+    let (language, region) = tag.split_once('-').unwrap_or((&tag, ""));
+    let locale = |names, twelve_hour, week_start| Ok(Locale { names, twelve_hour, week_start });
+    match (language, region) {
+	("c" | "posix", "")                  => Ok(DEFAULT),
+	("en", "")                           => Ok(DEFAULT),
+	("en", "us" | "ca" | "ph")           => locale(&ENGLISH, true, Weekday::Sun),
+	("en", "au" | "nz" | "in")           => locale(&ENGLISH, true, Weekday::Mon),
+	("en", _)                            => locale(&ENGLISH, false, Weekday::Mon),
+	("de", _)                            => locale(&GERMAN, false, Weekday::Mon),
+	("fr", "ca")                         => locale(&FRENCH, false, Weekday::Sun),
+	("fr", _)                            => locale(&FRENCH, false, Weekday::Mon),
+	("es", "us" | "mx")                  => locale(&SPANISH, false, Weekday::Sun),
+	("es", _)                            => locale(&SPANISH, false, Weekday::Mon),
+	("it", _)                            => locale(&ITALIAN, false, Weekday::Mon),
+	("nl", _)                            => locale(&DUTCH, false, Weekday::Mon),
+	("pt", "br")                         => locale(&PORTUGUESE, false, Weekday::Sun),
+	("pt", _)                            => locale(&PORTUGUESE, false, Weekday::Mon),
+	("sv", _)                            => locale(&SWEDISH, false, Weekday::Mon),
+	_ => Err(format!("unsupported locale '{}'; supported languages are en, de, fr, es, it, nl, pt and sv", tag)),
+    }
+}
+
+impl Locale {
+    pub fn weekday(&self, day: Weekday) -> &'static str {
+	self.names.weekdays[day.num_days_from_monday() as usize]
+    }
+
+    pub fn weekday_abbreviation(&self, day: Weekday) -> &'static str {
+	self.names.abbreviations[day.num_days_from_monday() as usize]
+    }
+
+    /// Name of a month, 1 to 12
+    pub fn month(&self, month: u32) -> &'static str {
+	self.names.months[month as usize - 1]
+    }
+
+    /// Days of the week, starting with the first
+    pub fn week(&self) -> impl Iterator<Item = Weekday> {
+	std::iter::successors(Some(self.week_start), |day| Some(day.succ())).take(7)
+    }
+
+    /// First day of the week that contains a date
+    pub fn week_of(&self, date: NaiveDate) -> NaiveDate {
+	date - Duration::days(date.weekday().days_since(self.week_start).into())
+    }
+
+    /// E.g. "Monday, 2025-03-03"
+    pub fn date(&self, date: NaiveDate) -> String {
+	format!("{}, {}", self.weekday(date.weekday()), date.format("%Y-%m-%d"))
+    }
+
+    /// E.g. "March 2025"
+    pub fn month_of_year(&self, date: NaiveDate) -> String {
+	format!("{} {}", self.month(date.month()), date.year())
+    }
+
+    /// E.g. "14:30" or "2:30 PM"
+    pub fn time(&self, time: NaiveTime) -> String {
+	if self.twelve_hour {
+	    time.format("%-I:%M %p").to_string()
+	} else {
+	    time.format("%H:%M").to_string()
+	}
+    }
+
+    /// E.g. "2025-03-03 to 2025-03-09"
+    pub fn date_range(&self, first: NaiveDate, last: NaiveDate) -> String {
+	format!("{} {} {}", first.format("%Y-%m-%d"), self.to(), last.format("%Y-%m-%d"))
+    }
+
+    /// E.g. "week of 2025-03-03"
+    pub fn week_title(&self, first: NaiveDate) -> String {
+	format!("{} {}", self.names.phrases.week_of, first.format("%Y-%m-%d"))
+    }
+
+    /// Word between the first and last day of a range, as in "2025-03-03 to 2025-03-09"
+    pub fn to(&self) -> &'static str {
+	self.names.phrases.to
+    }
+
+    pub fn all_day(&self) -> &'static str {
+	self.names.phrases.all_day
+    }
+
+    pub fn no_events(&self) -> &'static str {
+	self.names.phrases.no_events
+    }
+
+    /// Stand-in for the summary of an event without one
+    pub fn no_summary(&self) -> &'static str {
+	self.names.phrases.no_summary
+    }
+
+    /// Stand-in for the name of a calendar without one
+    pub fn schedule(&self) -> &'static str {
+	self.names.phrases.schedule
+    }
+
+    /// E.g. "today", "tomorrow" or "in 3 d", for an all-day event `days` days ahead
+    pub fn days_ahead(&self, days: i64) -> String {
+	match days {
+	    ..=0 => self.names.phrases.today.to_string(),
+	    1    => self.names.phrases.tomorrow.to_string(),
+	    days => format!("{} {} {}", self.names.phrases.ahead, days, self.names.phrases.units[0]),
+	}
+    }
+
+    /// E.g. "3 d 4 h", "2 h 15 m" or "5 m": the two largest units
+    pub fn span(&self, delta: Duration) -> String {
+	let minutes = delta.num_minutes().max(0);
+	let (days, hours, minutes) = (minutes / (24 * 60), (minutes / 60) % 24, minutes % 60);
+	let [day, hour, minute] = self.names.phrases.units;
+	if days > 0 {
+	    format!("{} {} {} {}", days, day, hours, hour)
+	} else if hours > 0 {
+	    format!("{} {} {} {}", hours, hour, minutes, minute)
+	} else {
+	    format!("{} {}", minutes, minute)
+	}
+    }
+
+    /// E.g. "in 2 h 15 m"
+    pub fn time_ahead(&self, delta: Duration) -> String {
+	format!("{} {}", self.names.phrases.ahead, self.span(delta))
+    }
+
+    /// E.g. "now (until 14:30)"
+    pub fn now_until(&self, end: NaiveTime) -> String {
+	format!("{} ({} {})", self.names.phrases.now, self.names.phrases.until, self.time(end))
+    }
+}
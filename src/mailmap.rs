@@ -0,0 +1,190 @@
+// Identity normalisation for attendees and organizers, following git's .mailmap format:
+//
+//   Proper Name <proper@email.xx>
+//   <proper@email.xx> <old@email.xx>
+//   Proper Name <proper@email.xx> <old@email.xx>
+//   Proper Name <proper@email.xx> Old Name <old@email.xx>
+
+use std::fs::read_to_string;
+
+use icalendar::{Event, Property};
+
+struct Entry {
+    proper_name: Option<String>,
+    proper_email: Option<String>,
+    /// Only map addresses that come with this common name (CN)
+    match_name: Option<String>,
+    match_email: String,
+}
+
+pub struct Mailmap {
+    entries: Vec<Entry>,
+}
+
+impl Mailmap {
+    pub fn load(filename: &str) -> std::io::Result<Self> {
+	Ok(Self::parse(&read_to_string(filename)?))
+    }
+
+    fn parse(text: &str) -> Self {
+	let mut entries = vec![];
+	for (number, line) in text.lines().enumerate() {
+	    let line = line.split('#').next().unwrap_or("").trim();
+	    if line.is_empty() {
+		continue;
+	    }
+	    match parse_line(line) {
+		Some(entry) => entries.push(entry),
+		None        => crate::warn(format!("Ignoring malformed mailmap line {}: {}", number + 1, line)),
+	    }
+	}
+	Self {
+	    entries,
+	}
+    }
+
+    /// Most specific entry for an address and common name: entries that also match the name
+    /// take precedence
+    fn lookup(&self, email: &str, name: Option<&str>) -> Option<&Entry> {
+	let candidates = || self.entries.iter().filter(|e| e.match_email.eq_ignore_ascii_case(email));
+	candidates()
+	    .filter(|e| e.match_name.is_some())
+	    .find(|e| name.is_some_and(|name| e.match_name.as_deref() == Some(name)))
+	    .or_else(|| candidates().find(|e| e.match_name.is_none()))
+    }
+
+    /// Normalise an ATTENDEE or ORGANIZER property
+    fn map_property(&self, property: &Property) -> Property {
+	let Some(email) = property.value().get(..7)
+	    .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+	    .map(|_| &property.value()[7..]) else {
+	    return property.clone();
+	};
+	let name = property.params().get("CN").map(|cn| cn.value().trim_matches('"'));
+	let Some(entry) = self.lookup(email, name) else {
+	    return property.clone();
+	};
+	let value = match entry.proper_email {
+	    Some(ref proper_email) => format!("mailto:{}", proper_email),
+	    None                   => property.value().to_string(),
+	};
+	let mut result = crate::property_with_value(property, &value);
+	if let Some(ref proper_name) = entry.proper_name {
+	    result.add_parameter("CN", proper_name);
+	}
+	if let (Some(_), Some(proper_email)) = (property.params().get("EMAIL"), &entry.proper_email) {
+	    result.add_parameter("EMAIL", proper_email);
+	}
+	result
+    }
+
+    /// Normalise all attendees and the organizer of an event
+    pub fn apply(&self, event: &Event) -> Event {
+	crate::map_properties(event, |property| match property.key() {
+	    "ATTENDEE" | "ORGANIZER" => Some(self.map_property(property)),
+	    _                        => Some(property.clone()),
+	})
+    }
+}
+
+/// Parse "[Name] <email> [[Name] <email>]"
+fn parse_line(line: &str) -> Option<Entry> {
+    let mut identities = vec![];
+    let mut rest = line;
+    while let Some(open) = rest.find('<') {
+	let close = open + rest[open..].find('>')?;
+	let name = rest[..open].trim();
+	identities.push(((!name.is_empty()).then(|| name.to_string()), rest[open + 1..close].trim().to_string()));
+	rest = &rest[close + 1..];
+    }
+    if !rest.trim().is_empty() {
+	return None;
+    }
+    match identities.len() {
+	1 => {
+	    // "Proper Name <email>": only fixes the name
+	    let (proper_name, email) = identities.pop()?;
+	    Some(Entry { proper_name: Some(proper_name?), proper_email: None, match_name: None, match_email: email })
+	},
+	2 => {
+	    let (match_name, match_email) = identities.pop()?;
+	    let (proper_name, proper_email) = identities.pop()?;
+	    Some(Entry { proper_name, proper_email: Some(proper_email), match_name, match_email })
+	},
+	_ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use icalendar::Component;
+
+    fn attendee(value: &str, cn: Option<&str>) -> Property {
+	let mut property = Property::new("ATTENDEE", value);
+	if let Some(cn) = cn {
+	    property.add_parameter("CN", cn);
+	}
+	property
+    }
+
+    #[test]
+    fn parses_all_line_forms() {
+	let mailmap = Mailmap::parse("\
+Jane Doe <jane@example.com>
+<joe@example.com> <joe@old.example.com>
+Ann Smith <ann@example.com> <as@example.com>   # renamed
+Bob <bob@example.com> Bobby <bobby@example.com>
+
+# comment only
+not an entry
+");
+	assert_eq!(mailmap.entries.len(), 4);
+	assert_eq!(mailmap.entries[0].proper_email, None);
+	assert_eq!(mailmap.entries[1].proper_name, None);
+	assert_eq!(mailmap.entries[2].match_email, "as@example.com");
+	assert_eq!(mailmap.entries[3].match_name.as_deref(), Some("Bobby"));
+    }
+
+    #[test]
+    fn maps_addresses_and_names() {
+	let mailmap = Mailmap::parse("Ann Smith <ann@example.com> <as@example.com>\nJane Doe <jane@example.com>");
+	let mapped = mailmap.map_property(&attendee("mailto:AS@example.com", Some("as")));
+	assert_eq!(mapped.value(), "mailto:ann@example.com");
+	assert_eq!(mapped.params().get("CN").map(|cn| cn.value()), Some("Ann Smith"));
+	// Only the name
+	let mapped = mailmap.map_property(&attendee("mailto:jane@example.com", None));
+	assert_eq!(mapped.value(), "mailto:jane@example.com");
+	assert_eq!(mapped.params().get("CN").map(|cn| cn.value()), Some("Jane Doe"));
+	// Neither other addresses nor other schemes
+	assert_eq!(mailmap.map_property(&attendee("mailto:other@example.com", None)).params().get("CN"), None);
+	assert_eq!(mailmap.map_property(&attendee("tel:+49123", None)).value(), "tel:+49123");
+    }
+
+    #[test]
+    fn prefers_entries_that_match_the_name() {
+	let mailmap = Mailmap::parse("\
+Team <team@example.com> <shared@example.com>
+Bob <bob@example.com> Bob <shared@example.com>");
+	assert_eq!(mailmap.map_property(&attendee("mailto:shared@example.com", Some("Bob"))).value(), "mailto:bob@example.com");
+	assert_eq!(mailmap.map_property(&attendee("mailto:shared@example.com", Some("Alice"))).value(), "mailto:team@example.com");
+    }
+
+    #[test]
+    fn applies_to_attendees_and_organizer_only() {
+	let mailmap = Mailmap::parse("<new@example.com> <old@example.com>");
+	let mut event = Event::new();
+	event.append_multi_property(attendee("mailto:old@example.com", None));
+	event.append_property(Property::new("ORGANIZER", "mailto:old@example.com"));
+	event.append_property(Property::new("CONTACT", "mailto:old@example.com"));
+	let event = mailmap.apply(&event);
+	assert_eq!(event.multi_properties()["ATTENDEE"][0].value(), "mailto:new@example.com");
+	assert_eq!(event.property_value("ORGANIZER"), Some("mailto:new@example.com"));
+	assert_eq!(event.property_value("CONTACT"), Some("mailto:old@example.com"));
+    }
+
+    #[test]
+    fn fails_to_load_missing_files() {
+	assert!(Mailmap::load("/nonexistent/.mailmap").is_err());
+    }
+}
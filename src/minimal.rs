@@ -0,0 +1,161 @@
+// Minimal, broadly compatible output: UTC times only, no VTIMEZONEs, alarms or X- properties
+
+use chrono::Utc;
+use icalendar::{Calendar, CalendarComponent, Component, Property};
+
+use crate::{recurrence, tz};
+
+/// Maximal number of instances of a recurring event to check for DST shifts
+const MAX_CHECKED_INSTANCES: u16 = 500;
+
+/// Maximal length of a content line in octets, excluding the line break
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Minimal version of the calendar: DATE-TIMEs in known time zones are converted to UTC, and
+/// VTIMEZONEs, VALARMs and X- properties are dropped.  Recurring events whose local start time
+/// would change across daylight saving time transitions keep their time zone (and its VTIMEZONE).
+pub fn minimize(calendar: &Calendar) -> Calendar {
+    let mut result = Calendar::new();
+    result.properties = calendar.properties.iter().filter(|p| !is_extension(p)).cloned().collect();
+
+    for component in &calendar.components {
+	match component {
+	    CalendarComponent::Event(event) => { result.push(minimize_event(event)); },
+	    CalendarComponent::Todo(todo)   => { result.push(minimize_component(todo, true)); },
+	    _ if tz::vtimezone_tzid(component).is_some() => {},
+	    _ => { result.push(component.clone()); },
+	}
+    }
+    // Only the time zones that are still needed
+    let vtimezones: Vec<CalendarComponent> = calendar.components.iter().filter(|c| tz::vtimezone_tzid(c).is_some()).cloned().collect();
+    result.components.splice(0..0, vtimezones);
+    tz::retain_referenced_timezones(&mut result);
+    result
+}
+
+fn is_extension(property: &Property) -> bool {
+    property.key().to_uppercase().starts_with("X-")
+}
+
+/// Minimal event; falls back to keeping time zones if conversion to UTC would move occurrences
+fn minimize_event(event: &icalendar::Event) -> icalendar::Event {
+    let converted = minimize_component(event, true);
+    let Some(tz) = event.properties().get("DTSTART")
+	.filter(|_| event.property_value("RRULE").is_some())
+	.and_then(|dtstart| dtstart.params().get("TZID"))
+	.and_then(|tzid| recurrence::resolve_tz(tzid.value())) else {
+	return converted;
+    };
+    if recurrence::wall_clock_shifts(&converted, tz, MAX_CHECKED_INSTANCES).is_empty() {
+	return converted;
+    }
+    eprintln!("Keeping time zone {} for recurring event {}, whose local time would otherwise shift",
+	      tz, event.property_value("UID").unwrap_or("(no UID)"));
+    minimize_component(event, false)
+}
+
+/// Copy of the component without sub-components and X- properties; with `to_utc`, date-times in
+/// known time zones are converted to UTC, and so is the UNTIL of recurrence rules
+fn minimize_component<C: Component + Default>(component: &C, to_utc: bool) -> C {
+    let dtstart_tz = component.properties().get("DTSTART")
+	.filter(|dtstart| !recurrence::is_date_value(dtstart))
+	.and_then(|dtstart| dtstart.params().get("TZID"))
+	.and_then(|tzid| recurrence::resolve_tz(tzid.value()));
+    let convert = |property: &Property| match (to_utc, dtstart_tz) {
+	(true, Some(tz)) if property.key() == "RRULE" => rule_to_utc(property, tz),
+	(true, _) => property_to_utc(property),
+	(false, _) => property.clone(),
+    };
+    let mut result = C::default();
+    for property in component.properties().values().filter(|p| !is_extension(p)) {
+	result.append_property(convert(property));
+    }
+    for property in component.multi_properties().values().flatten().filter(|p| !is_extension(p)) {
+	result.append_multi_property(convert(property));
+    }
+    result
+}
+
+/// A recurrence rule of an event whose DTSTART moves from `tz` to UTC, with its UNTIL in UTC as
+/// well: RFC 5545 wants UNTIL to be in UTC then, and some producers write it in local time or as
+/// a DATE (taken to include that whole day)
+fn rule_to_utc(rrule: &Property, tz: chrono_tz::Tz) -> Property {
+    let parts: Vec<String> = rrule.value().split(';')
+	.map(|part| match part.split_once('=') {
+	    Some((key, until)) if key.eq_ignore_ascii_case("UNTIL") && !until.ends_with('Z') => {
+		let until = match until.len() {
+		    8 => format!("{}T235959", until),
+		    _ => until.to_string(),
+		};
+		match recurrence::parse_date_time_value(&until, tz.into()) {
+		    Some(until) => format!("{}={}", key, until.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ")),
+		    None        => part.to_string(),
+		}
+	    },
+	    _ => part.to_string(),
+	})
+	.collect();
+    let mut result = Property::new(rrule.key(), parts.join(";"));
+    for (key, parameter) in rrule.params() {
+	result.add_parameter(key, parameter.value());
+    }
+    result
+}
+
+/// Convert a DATE-TIME (or DATE-TIME list) property with a known TZID to UTC
+fn property_to_utc(property: &Property) -> Property {
+    let Some(tzid) = property.params().get("TZID") else {
+	return property.clone();
+    };
+    let Some(tz) = recurrence::resolve_tz(tzid.value()) else {
+	return property.clone();
+    };
+    if recurrence::is_date_value(property) {
+	return property.clone();
+    }
+    let values: Option<Vec<String>> = property.value().split(',')
+	.map(|v| recurrence::parse_date_time_value(v.trim(), tz.into())
+	     .map(|t| t.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()))
+	.collect();
+    let Some(values) = values else {
+	return property.clone();
+    };
+    let mut result = Property::new(property.key(), values.join(","));
+    for (key, parameter) in property.params() {
+	if key != "TZID" {
+	    result.add_parameter(key, parameter.value());
+	}
+    }
+    result
+}
+
+/// Re-fold serialised ICS text so that no line, continuation lines included, exceeds 75 octets
+pub fn fold_strictly(text: &str) -> String {
+    let unfolded = text.replace("\r\n ", "").replace("\r\n\t", "");
+    let mut result = String::with_capacity(unfolded.len() + unfolded.len() / MAX_LINE_OCTETS * 3);
+    for line in unfolded.split_terminator("\r\n") {
+	result.push_str(&fold_line(line));
+	result.push_str("\r\n");
+    }
+    result
+}
+
+/// Fold one content line into lines of at most 75 octets, without a final line break
+pub fn fold_line(line: &str) -> String {
+    let mut result = String::with_capacity(line.len() + line.len() / MAX_LINE_OCTETS * 3);
+    let mut rest = line;
+    let mut limit = MAX_LINE_OCTETS;
+    while rest.len() > limit {
+	let mut split = limit;
+	while !rest.is_char_boundary(split) {
+	    split -= 1;
+	}
+	result.push_str(&rest[..split]);
+	result.push_str("\r\n ");
+	rest = &rest[split..];
+	// Continuation lines start with a space
+	limit = MAX_LINE_OCTETS - 1;
+    }
+    result.push_str(rest);
+    result
+}
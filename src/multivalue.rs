@@ -0,0 +1,148 @@
+// Properties with comma-separated lists of values, such as CATEGORIES and RESOURCES.  Producers
+// disagree on whether to write "CATEGORIES:A,B" or one CATEGORIES line per value; splitting and
+// joining brings them into one form.
+
+use icalendar::{Component, Property};
+use std::sync::Mutex;
+
+/// Properties whose values `join` has put together, and whose commas separate values
+static JOINED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Values of a property; the icalendar crate unescapes "\," as well, so all commas separate values
+fn values(property: &Property) -> impl Iterator<Item = &str> {
+    property.value().split(',').map(str::trim).filter(|value| !value.is_empty())
+}
+
+fn with_parameters(key: &str, value: &str, template: &Property) -> Property {
+    let mut result = Property::new(key, value);
+    for (key, parameter) in template.params() {
+	result.add_parameter(key, parameter.value());
+    }
+    result
+}
+
+/// Copy of a component without the given properties, which are passed to `f` instead
+fn partition<'a, C, F>(component: &'a C, keys: &[String], mut f: F) -> C
+where C: Component + Default,
+      F: FnMut(&'a Property) {
+    let mut result = C::default();
+    for property in component.properties().values() {
+	if keys.iter().any(|key| key.eq_ignore_ascii_case(property.key())) {
+	    f(property);
+	} else {
+	    result.append_property(property.clone());
+	}
+    }
+    for property in component.multi_properties().values().flatten() {
+	if keys.iter().any(|key| key.eq_ignore_ascii_case(property.key())) {
+	    f(property);
+	} else {
+	    result.append_multi_property(property.clone());
+	}
+    }
+    for child in component.components() {
+	result.append_component(child.clone());
+    }
+    result
+}
+
+/// One property per value, e.g. "CATEGORIES:A,B" becomes "CATEGORIES:A" and "CATEGORIES:B";
+/// repeated values are dropped
+pub fn split<C: Component + Default>(component: &C, keys: &[String]) -> C {
+    let mut properties: Vec<Property> = vec![];
+    let mut result = partition(component, keys, |property| {
+	for value in values(property) {
+	    if !properties.iter().any(|p| p.key() == property.key() && p.value() == value) {
+		properties.push(with_parameters(property.key(), value, property));
+	    }
+	}
+    });
+    for property in properties {
+	result.append_multi_property(property);
+    }
+    result
+}
+
+/// One property with all values, e.g. "CATEGORIES:A" and "CATEGORIES:B" become "CATEGORIES:A,B";
+/// properties whose parameters (such as LANGUAGE) differ are kept apart, and repeated values are
+/// dropped
+pub fn join<C: Component + Default>(component: &C, keys: &[String]) -> C {
+    let mut joined = JOINED.lock().unwrap();
+    for key in keys {
+	if !joined.iter().any(|other| other.eq_ignore_ascii_case(key)) {
+	    joined.push(key.clone());
+	}
+    }
+    drop(joined);
+    let mut groups: Vec<(&Property, Vec<&str>)> = vec![];
+    let mut result = partition(component, keys, |property| {
+	let same_kind = |p: &Property| p.key() == property.key()
+	    && p.params().len() == property.params().len()
+	    && p.params().iter().all(|(key, parameter)| property.params().get(key).is_some_and(|other| other.value() == parameter.value()));
+	let index = match groups.iter().position(|(first, _)| same_kind(first)) {
+	    Some(index) => index,
+	    None        => { groups.push((property, vec![])); groups.len() - 1 },
+	};
+	for value in values(property) {
+	    if !groups[index].1.contains(&value) {
+		groups[index].1.push(value);
+	    }
+	}
+    });
+    for (first, values) in groups.iter().filter(|(_, values)| !values.is_empty()) {
+	result.append_multi_property(with_parameters(first.key(), &values.join(","), first));
+    }
+    result
+}
+
+/// Undo the escaping of the commas that separate the values of joined properties in serialised
+/// text: the icalendar crate escapes all commas in TEXT values, which turns "A,B" into one value.
+/// Other properties keep their escaped commas.
+pub fn unescape_separators(text: &str) -> String {
+    let joined = JOINED.lock().unwrap();
+    if joined.is_empty() {
+	return text.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut lines = text.split_inclusive('\n').peekable();
+    while let Some(first) = lines.next() {
+	let mut physical = vec![first];
+	while let Some(continuation) = lines.next_if(|line| line.starts_with([' ', '\t'])) {
+	    physical.push(continuation);
+	}
+	let line_break = &first[first.trim_end_matches(['\r', '\n']).len()..];
+	let logical: String = physical.iter().enumerate()
+	    .map(|(i, line)| &line.trim_end_matches(['\r', '\n'])[if i == 0 { 0 } else { 1 }..])
+	    .collect();
+	let Some(value_start) = value_start(&logical, &joined).filter(|&start| logical[start..].contains("\\,")) else {
+	    result.extend(physical);
+	    continue;
+	};
+	let fixed = format!("{}{}", &logical[..value_start], logical[value_start..].replace("\\,", ","));
+	// Unfolded lines (as with --git-friendly) stay unfolded
+	match physical.len() {
+	    1 => result.push_str(&fixed),
+	    _ => result.push_str(&crate::minimal::fold_line(&fixed).replace("\r\n", line_break)),
+	}
+	result.push_str(line_break);
+    }
+    result
+}
+
+/// Start of the value in a content line of one of the `keys`
+fn value_start(line: &str, keys: &[String]) -> Option<usize> {
+    let name_end = line.find([';', ':'])?;
+    if !keys.iter().any(|key| key.eq_ignore_ascii_case(&line[..name_end])) {
+	return None;
+    }
+    // Parameter values may contain colons in quotes
+    let mut quoted = false;
+    for (i, c) in line[name_end..].char_indices() {
+	match c {
+	    '"'            => quoted = !quoted,
+	    ':' if !quoted => return Some(name_end + i + 1),
+	    _              => {},
+	}
+    }
+    None
+}
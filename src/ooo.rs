@@ -0,0 +1,55 @@
+// Out-of-office and working-location events, as exported by Google Calendar (X-GOOGLE-EVENT-TYPE,
+// after the API's event types) and Exchange (X-MICROSOFT-CDO-BUSYSTATUS:OOF).  Both are usually
+// all-day events: out-of-office ones block time, working-location ones are transparent.
+
+use chrono::{NaiveDate, Utc};
+use icalendar::{Component, Event, Property};
+
+const GOOGLE_EVENT_TYPE: &str = "X-GOOGLE-EVENT-TYPE";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    OutOfOffice,
+    WorkingLocation,
+}
+
+/// The special kind of an event, if any
+pub fn kind(event: &Event) -> Option<Kind> {
+    match event.property_value(GOOGLE_EVENT_TYPE).map(str::trim) {
+	Some(t) if t.eq_ignore_ascii_case("outOfOffice")     => return Some(Kind::OutOfOffice),
+	Some(t) if t.eq_ignore_ascii_case("workingLocation") => return Some(Kind::WorkingLocation),
+	_ => {},
+    }
+    ["X-MICROSOFT-CDO-BUSYSTATUS", "X-MICROSOFT-CDO-INTENDEDSTATUS"].iter()
+	.any(|key| event.property_value(key).is_some_and(|status| status.trim().eq_ignore_ascii_case("OOF")))
+	.then_some(Kind::OutOfOffice)
+}
+
+fn date_property(key: &str, date: NaiveDate) -> Property {
+    let mut property = Property::new(key, date.format("%Y%m%d").to_string());
+    property.add_parameter("VALUE", "DATE");
+    property
+}
+
+/// All-day out-of-office event from `from` to `to` (inclusive), recognisable by both Google and
+/// Exchange, with the message for declined invitations as its DESCRIPTION
+pub fn out_of_office(from: NaiveDate, to: NaiveDate, summary: &str, message: Option<&str>) -> Result<Event, String> {
+    if to < from {
+	return Err(format!("{} is before {}", to, from));
+    }
+    let end = to.succ_opt().ok_or_else(|| format!("{} is out of range", to))?;
+    let mut event = Event::new();
+    event.uid(&format!("{:016x}@icalm", crate::fnv1a(&format!("ooo\u{1f}{}\u{1f}{}", from, to))));
+    event.timestamp(Utc::now());
+    event.append_property(date_property("DTSTART", from));
+    event.append_property(date_property("DTEND", end));
+    event.summary(summary);
+    if let Some(message) = message {
+	event.description(message);
+    }
+    event.append_property(Property::new("TRANSP", "OPAQUE"));
+    event.append_property(Property::new(GOOGLE_EVENT_TYPE, "outOfOffice"));
+    event.append_property(Property::new("X-MICROSOFT-CDO-BUSYSTATUS", "OOF"));
+    event.append_property(Property::new("X-MICROSOFT-CDO-ALLDAYEVENT", "TRUE"));
+    Ok(event.done())
+}
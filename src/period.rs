@@ -0,0 +1,43 @@
+// Reporting periods: ISO weeks, months, and (fiscal) quarters and years
+
+use chrono::{Datelike, NaiveDate};
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Grouping {
+    /// ISO 8601 week, e.g. "2025-W09"
+    Week,
+    /// E.g. "2025-03"
+    Month,
+    /// E.g. "2025-Q1", or "FY2024-25-Q4" for fiscal years not starting in January
+    Quarter,
+    /// E.g. "2025", or "FY2024-25" for fiscal years not starting in January
+    Year,
+}
+
+/// How to label the period that contains a date
+#[derive(Clone, Copy)]
+pub struct Periods {
+    pub grouping: Grouping,
+    /// Month (1 to 12) in which the (fiscal) year starts
+    pub year_start: u32,
+}
+
+impl Periods {
+    /// Label of the period that contains `date`; labels sort in chronological order
+    pub fn label(&self, date: NaiveDate) -> String {
+	// Months since the start of the fiscal year, and the calendar year in which it starts
+	let offset = (date.month() + 12 - self.year_start) % 12;
+	let first_year = if date.month() >= self.year_start { date.year() } else { date.year() - 1 };
+	let year = match self.year_start {
+	    1 => first_year.to_string(),
+	    _ => format!("FY{}-{:02}", first_year, (first_year + 1).rem_euclid(100)),
+	};
+	match self.grouping {
+	    Grouping::Week    => format!("{}-W{:02}", date.iso_week().year(), date.iso_week().week()),
+	    Grouping::Month   => format!("{}-{:02}", date.year(), date.month()),
+	    Grouping::Quarter => format!("{}-Q{}", year, offset / 3 + 1),
+	    Grouping::Year    => year,
+	}
+    }
+}
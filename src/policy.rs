@@ -0,0 +1,103 @@
+// Property policies (TOML): which properties components must have, must not have, or may only
+// have up to a certain length, e.g.
+//
+//   [vevent]
+//   required = ["SUMMARY", "LOCATION"]
+//   forbidden = ["ATTENDEE"]
+//   max-length = { SUMMARY = 80 }
+//   defaults = { LOCATION = "TBA" }
+
+use std::collections::BTreeMap;
+
+use icalendar::{Component, Event, Property};
+use serde::Deserialize;
+
+/// Rules by component kind ("VEVENT", "VTODO", ...)
+pub type Policy = BTreeMap<String, Rules>;
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Rules {
+    #[serde(default)]
+    pub required: Vec<String>,
+    #[serde(default)]
+    pub forbidden: Vec<String>,
+    /// Maximal length of values, in characters, by property
+    #[serde(default)]
+    pub max_length: BTreeMap<String, usize>,
+    /// Values to add for missing required properties when fixing
+    #[serde(default)]
+    pub defaults: BTreeMap<String, String>,
+}
+
+impl Rules {
+    fn normalize(self) -> Self {
+	let upper = |names: Vec<String>| names.into_iter().map(|name| name.to_uppercase()).collect();
+	Rules {
+	    required: upper(self.required),
+	    forbidden: upper(self.forbidden),
+	    max_length: self.max_length.into_iter().map(|(name, length)| (name.to_uppercase(), length)).collect(),
+	    defaults: self.defaults.into_iter().map(|(name, value)| (name.to_uppercase(), value)).collect(),
+	}
+    }
+}
+
+pub fn load(filename: &str) -> Result<Policy, String> {
+    let text = std::fs::read_to_string(filename).map_err(|e| format!("cannot read {}: {}", filename, e))?;
+    let policy: Policy = toml::from_str(&text).map_err(|e| format!("{}: {}", filename, e))?;
+    Ok(policy.into_iter().map(|(kind, rules)| (kind.to_uppercase(), rules.normalize())).collect())
+}
+
+/// Describe how a component (presented as an event) violates the rules
+pub fn violations(event: &Event, rules: &Rules) -> Vec<String> {
+    let mut result = vec![];
+    for name in &rules.required {
+	if !has_property(event, name) {
+	    result.push(format!("lacks required {}", name));
+	}
+    }
+    for name in &rules.forbidden {
+	if has_property(event, name) {
+	    result.push(format!("has forbidden {}", name));
+	}
+    }
+    for (name, max_length) in &rules.max_length {
+	for property in crate::all_properties(event).filter(|p| p.key() == name) {
+	    let length = property.value().chars().count();
+	    if length > *max_length {
+		result.push(format!("{} has {} characters; at most {} are allowed", name, length, max_length));
+	    }
+	}
+    }
+    result
+}
+
+fn has_property(event: &Event, name: &str) -> bool {
+    event.properties().contains_key(name) || event.multi_properties().get(name).is_some_and(|ps| !ps.is_empty())
+}
+
+/// Fix what can be fixed: drop forbidden properties, shorten values that are too long, and add
+/// defaults for missing required properties
+pub fn fix(event: &Event, rules: &Rules) -> Event {
+    let mut fixed = crate::map_own_properties(event, |property| {
+	if rules.forbidden.iter().any(|name| name == property.key()) {
+	    return None;
+	}
+	match rules.max_length.get(property.key()) {
+	    Some(max_length) if property.value().chars().count() > *max_length => {
+		let mut shortened = Property::new(property.key(), property.value().chars().take(*max_length).collect::<String>());
+		for param in property.params().values() {
+		    shortened.append_parameter(param.clone());
+		}
+		Some(shortened)
+	    },
+	    _ => Some(property.clone()),
+	}
+    });
+    for name in &rules.required {
+	if let Some(value) = rules.defaults.get(name).filter(|_| !has_property(&fixed, name)) {
+	    fixed.append_property(Property::new(name, value));
+	}
+    }
+    fixed
+}
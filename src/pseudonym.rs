@@ -0,0 +1,62 @@
+// Keyed pseudonyms: the same identity (or value) maps to the same pseudonym whenever the same key
+// is used, across runs and files, but cannot be traced back without the key (HMAC-SHA256)
+
+use icalendar::Property;
+use ring::hmac;
+
+/// Length of pseudonyms, in hexadecimal digits
+const LENGTH: usize = 16;
+
+/// Environment variable with the key, if it is not given in a file
+pub const KEY_VARIABLE: &str = "ICALM_PSEUDONYM_KEY";
+
+/// The key in `file` (without the final line break), or else in KEY_VARIABLE; never taken from the
+/// command line, where other users could see it
+pub fn load_key(file: Option<&str>) -> Result<String, String> {
+    let key = match file {
+	Some(file) => std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?
+	    .trim_end_matches(['\r', '\n']).to_string(),
+	None       => std::env::var(KEY_VARIABLE).map_err(|_| format!("no key: give a key file, or set {}", KEY_VARIABLE))?,
+    };
+    match key.is_empty() {
+	true  => Err("empty key".to_string()),
+	false => Ok(key),
+    }
+}
+
+pub struct Pseudonyms {
+    key: hmac::Key,
+}
+
+impl Pseudonyms {
+    pub fn new(key: &str) -> Self {
+	Pseudonyms { key: hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes()) }
+    }
+
+    /// Pseudonym of a value; values that differ only in case or surrounding space are the same
+    pub fn of(&self, value: &str) -> String {
+	let tag = hmac::sign(&self.key, value.trim().to_lowercase().as_bytes());
+	tag.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect::<String>()[..LENGTH].to_string()
+    }
+
+    /// A calendar user (ATTENDEE, ORGANIZER) under the pseudonym of their address, keeping the
+    /// parameters that do not identify them (such as roles and replies)
+    pub fn calendar_user(&self, property: &Property) -> Property {
+	let address = crate::mailto_address(property).unwrap_or(property.value());
+	let pseudonym = self.of(address);
+	let mut result = Property::new(property.key(), format!("mailto:{}@invalid", pseudonym));
+	for param in property.params().values().filter(|param| !crate::IDENTIFYING_PARAMETERS.contains(&param.key())) {
+	    result.append_parameter(param.clone());
+	}
+	result.add_parameter("CN", &pseudonym);
+	result
+    }
+
+    /// A property under the pseudonym of its value
+    pub fn property(&self, property: &Property) -> Property {
+	match property.key() {
+	    "ATTENDEE" | "ORGANIZER" => self.calendar_user(property),
+	    _                        => crate::property_with_value(property, &self.of(property.value())),
+	}
+    }
+}
@@ -0,0 +1,244 @@
+// Date/time interpretation of event properties and expansion of recurring events
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use icalendar::{Component, Property};
+use rrule::{RRule, RRuleSet, Tz, Unvalidated};
+use std::collections::HashSet;
+
+/// One instance of a (possibly recurring) event
+pub struct Occurrence {
+    /// Start, in the time zone the event was declared in
+    pub start: DateTime<Tz>,
+    pub duration: Duration,
+    /// DATE-valued (rather than DATE-TIME) event
+    pub all_day: bool,
+}
+
+impl Occurrence {
+    pub fn end(&self) -> DateTime<Tz> {
+	self.start + self.duration
+    }
+}
+
+/// Map a TZID to an IANA time zone.  Tolerates the "/vendor/.../Europe/Berlin" style prefixes
+/// that some producers emit, and Windows zone names ("W. Europe Standard Time").
+pub fn resolve_tz(tzid: &str) -> Option<chrono_tz::Tz> {
+    let tzid = tzid.trim_matches('"');
+    if let Ok(tz) = tzid.parse::<chrono_tz::Tz>() {
+	return Some(tz);
+    }
+    if let Some(tz) = crate::tz::windows_zone(tzid) {
+	return Some(tz);
+    }
+    // Try successively shorter suffixes: "/mozilla.org/20050126_1/Europe/Berlin" -> "Europe/Berlin"
+    let mut rest = tzid;
+    while let Some(pos) = rest.find('/') {
+	rest = &rest[pos + 1..];
+	if let Ok(tz) = rest.parse::<chrono_tz::Tz>() {
+	    return Some(tz);
+	}
+    }
+    None
+}
+
+/// Parse an iCalendar DURATION value (e.g., "PT15M", "-P1DT2H", "P2W")
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (sign, rest) = match value.strip_prefix('-') {
+	Some(rest) => (-1, rest),
+	None       => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let rest = rest.strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+	match c {
+	    '0'..='9' => number.push(c),
+	    'T' => in_time = true,
+	    _ => {
+		let n: i64 = number.parse().ok()?;
+		number.clear();
+		total += match (c, in_time) {
+		    ('W', false) => Duration::weeks(n),
+		    ('D', false) => Duration::days(n),
+		    ('H', true)  => Duration::hours(n),
+		    ('M', true)  => Duration::minutes(n),
+		    ('S', true)  => Duration::seconds(n),
+		    _            => return None,
+		};
+	    }
+	}
+    }
+    if !number.is_empty() {
+	return None;
+    }
+    Some(total * sign)
+}
+
+/// Format a duration as an iCalendar DURATION value (e.g., "PT15M", "P1DT2H")
+pub fn format_duration(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let seconds = duration.num_seconds().abs();
+    let (days, hours, minutes, seconds) = (seconds / 86400, (seconds / 3600) % 24, (seconds / 60) % 60, seconds % 60);
+    let mut result = format!("{}P", sign);
+    if days > 0 {
+	result += &format!("{}D", days);
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || days == 0 {
+	result.push('T');
+	if hours > 0 {
+	    result += &format!("{}H", hours);
+	}
+	if minutes > 0 {
+	    result += &format!("{}M", minutes);
+	}
+	if seconds > 0 || (hours == 0 && minutes == 0) {
+	    result += &format!("{}S", seconds);
+	}
+    }
+    result
+}
+
+/// Is this a DATE (rather than DATE-TIME) valued property?
+pub fn is_date_value(property: &Property) -> bool {
+    match property.params().get("VALUE") {
+	Some(value) => value.value().eq_ignore_ascii_case("DATE"),
+	None        => property.value().len() == 8,
+    }
+}
+
+/// Time zone a DATE-TIME value is expressed in: UTC for "Z" values, the TZID if we recognise
+/// it, and local (floating) time otherwise
+pub fn property_tz(property: &Property) -> Tz {
+    if property.value().ends_with('Z') {
+	return Tz::UTC;
+    }
+    match property.params().get("TZID") {
+	Some(tzid) => match resolve_tz(tzid.value()) {
+	    Some(tz) => tz.into(),
+	    None     => {
+		crate::warn(format!("Unknown time zone '{}'; assuming UTC", tzid.value()));
+		Tz::UTC
+	    }
+	},
+	None => Tz::LOCAL,
+    }
+}
+
+/// Parse a single DATE or DATE-TIME value in the given zone
+pub fn parse_date_time_value(value: &str, tz: Tz) -> Option<DateTime<Tz>> {
+    let naive = if value.len() == 8 {
+	NaiveDate::parse_from_str(value, "%Y%m%d").ok()?.and_hms_opt(0, 0, 0)?
+    } else {
+	NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()?
+    };
+    // Nonexistent local times (in a DST gap) are shifted forward, as RFC 5545 asks
+    tz.from_local_datetime(&naive).earliest()
+	.or_else(|| tz.from_local_datetime(&(naive + Duration::hours(1))).earliest())
+}
+
+/// Interpret a DTSTART/DTEND/RECURRENCE-ID-like property
+pub fn property_date_time(property: &Property) -> Option<DateTime<Tz>> {
+    parse_date_time_value(property.value(), property_tz(property))
+}
+
+/// All values of a (possibly comma-separated, possibly repeated) EXDATE/RDATE-like property
+fn date_time_list<C: Component>(component: &C, key: &str) -> Vec<DateTime<Tz>> {
+    let properties = component.multi_properties().get(key).into_iter().flatten()
+	.chain(component.properties().get(key));
+    let mut result = vec![];
+    for property in properties {
+	let tz = property_tz(property);
+	result.extend(property.value().split(',').filter_map(|v| parse_date_time_value(v.trim(), tz)));
+    }
+    result
+}
+
+/// Start, duration and all-day flag of an event (or other component with DTSTART)
+pub fn event_timing<C: Component>(event: &C) -> Option<(DateTime<Tz>, Duration, bool)> {
+    let dtstart = event.properties().get("DTSTART")?;
+    let start = property_date_time(dtstart)?;
+    let all_day = is_date_value(dtstart);
+    let duration = if let Some(end) = event.properties().get("DTEND").and_then(property_date_time) {
+	end - start
+    } else if let Some(duration) = event.property_value("DURATION").and_then(parse_duration) {
+	duration
+    } else if all_day {
+	Duration::days(1)
+    } else {
+	Duration::zero()
+    };
+    Some((start, duration, all_day))
+}
+
+/// Build the recurrence set of an event from its DTSTART, RRULE, RDATE and EXDATE properties.
+/// Returns None for events without DTSTART.
+pub fn recurrence_set<C: Component>(event: &C) -> Option<RRuleSet> {
+    let (start, _, _) = event_timing(event)?;
+    let mut set = RRuleSet::new(start);
+    if let Some(rrule) = event.property_value("RRULE") {
+	match rrule.parse::<RRule<Unvalidated>>().and_then(|r| r.validate(start)) {
+	    Ok(rrule) => set = set.rrule(rrule),
+	    Err(e)    => crate::warn(format!("Ignoring unusable RRULE '{}' in event {}: {}",
+					       rrule, event.property_value("UID").unwrap_or("(no UID)"), e)),
+	}
+    }
+    if set.get_rrule().is_empty() {
+	set = set.rdate(start);
+    }
+    set = date_time_list(event, "RDATE").into_iter().fold(set, RRuleSet::rdate);
+    Some(set.set_exdates(date_time_list(event, "EXDATE")))
+}
+
+/// Occurrences of an event that have not ended by `after` and start before `before`, earliest
+/// first, at most `limit` of them
+pub fn occurrences<C: Component>(event: &C, after: DateTime<Utc>, before: Option<DateTime<Utc>>, limit: u16) -> Vec<Occurrence> {
+    let Some((_, duration, all_day)) = event_timing(event) else {
+	return vec![];
+    };
+    let Some(mut set) = recurrence_set(event) else {
+	return vec![];
+    };
+    // Include occurrences that are still in progress
+    set = set.after((after - duration).with_timezone(&Tz::UTC));
+    if let Some(before) = before {
+	set = set.before(before.with_timezone(&Tz::UTC));
+    }
+    set.all(limit).dates.into_iter()
+	.filter(|start| *start + duration > after || duration.is_zero())
+	.map(|start| Occurrence { start, duration, all_day })
+	.collect()
+}
+
+/// Occurrences that overrides (components with RECURRENCE-ID) replace, by UID and start
+pub fn overridden<'a, C: Component + 'a>(components: impl IntoIterator<Item = &'a C>) -> HashSet<(&'a str, DateTime<Utc>)> {
+    components.into_iter()
+	.filter_map(|component| Some((component.get_uid()?, property_date_time(component.properties().get("RECURRENCE-ID")?)?.with_timezone(&Utc))))
+	.collect()
+}
+
+/// Is this occurrence of a series replaced by one of the `overridden` ones?  Occurrences of the
+/// overrides themselves never are.
+pub fn is_overridden<C: Component>(overridden: &HashSet<(&str, DateTime<Utc>)>, event: &C, occurrence: &Occurrence) -> bool {
+    !event.properties().contains_key("RECURRENCE-ID")
+	&& event.get_uid().is_some_and(|uid| overridden.contains(&(uid, occurrence.start.with_timezone(&Utc))))
+}
+
+/// Dates of occurrences whose wall-clock start time in `declared` differs from that of the
+/// first occurrence, together with the first occurrence's and the deviating local start.
+/// Typically the symptom of a series anchored in UTC rather than in its intended time zone.
+pub fn wall_clock_shifts<C: Component>(event: &C, declared: chrono_tz::Tz, limit: u16) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let Some(set) = recurrence_set(event) else {
+	return vec![];
+    };
+    let local_starts: Vec<NaiveDateTime> = set.all(limit).dates.iter()
+	.map(|start| start.with_timezone(&declared).naive_local())
+	.collect();
+    let Some(first) = local_starts.first().copied() else {
+	return vec![];
+    };
+    local_starts.into_iter()
+	.filter(|start| start.time() != first.time())
+	.map(|start| (first, start))
+	.collect()
+}
@@ -0,0 +1,87 @@
+// Relationships between components (RELATED-TO, RFC 5545, 3.8.4.5)
+
+use icalendar::{CalendarComponent, Component};
+use std::collections::{HashMap, HashSet};
+
+/// A RELATED-TO link from a component to another one
+pub struct Link {
+    /// PARENT (the default), CHILD, SIBLING or an extension
+    pub reltype: String,
+    pub uid: String,
+}
+
+fn links_of<C: Component>(component: &C) -> Vec<Link> {
+    component.multi_properties().get("RELATED-TO").into_iter().flatten()
+	.chain(component.properties().get("RELATED-TO"))
+	.map(|property| Link {
+	    reltype: property.params().get("RELTYPE").map_or("PARENT".to_string(), |t| t.value().to_uppercase()),
+	    uid: property.value().trim().to_string(),
+	})
+	.collect()
+}
+
+/// RELATED-TO links of a top-level component
+pub fn links(component: &CalendarComponent) -> Vec<Link> {
+    match component {
+	CalendarComponent::Event(event) => links_of(event),
+	CalendarComponent::Todo(todo)   => links_of(todo),
+	CalendarComponent::Venue(venue) => links_of(venue),
+	CalendarComponent::Other(other) => links_of(other),
+	_ => vec![],
+    }
+}
+
+/// UIDs of a component, its ancestors and its descendants, following PARENT links (and CHILD
+/// links in the opposite direction) transitively
+pub fn family(components: &[CalendarComponent], uid: &str) -> HashSet<String> {
+    // (parent, child) pairs
+    let mut edges: Vec<(&str, &str)> = vec![];
+    let all_links: Vec<(&str, Vec<Link>)> = components.iter()
+	.filter_map(|component| crate::component_uid(component).map(|own| (own, links(component))))
+	.collect();
+    for (own, links) in &all_links {
+	for link in links {
+	    match link.reltype.as_str() {
+		"PARENT" => edges.push((&link.uid, own)),
+		"CHILD"  => edges.push((own, &link.uid)),
+		_        => {},
+	    }
+	}
+    }
+    let mut result = HashSet::from([uid.to_string()]);
+    for (from, to) in [(0, 1), (1, 0)] {
+	// Upwards to the ancestors, then downwards to the descendants
+	let mut next: HashMap<&str, Vec<&str>> = HashMap::new();
+	for edge in &edges {
+	    let edge = [edge.0, edge.1];
+	    next.entry(edge[to]).or_default().push(edge[from]);
+	}
+	let mut seen = HashSet::from([uid]);
+	let mut pending = vec![uid];
+	while let Some(current) = pending.pop() {
+	    for &related in next.get(current).into_iter().flatten() {
+		if seen.insert(related) {
+		    result.insert(related.to_string());
+		    pending.push(related);
+		}
+	    }
+	}
+    }
+    result
+}
+
+/// Links of the remaining components to components that are gone, as (UID, RELATED-TO) pairs
+pub fn dangling(remaining: &[CalendarComponent], gone: &HashSet<String>) -> Vec<(String, String)> {
+    let mut result = vec![];
+    for component in remaining {
+	let Some(own) = crate::component_uid(component) else {
+	    continue;
+	};
+	for link in links(component) {
+	    if gone.contains(&link.uid) && !result.iter().any(|(u, l)| u == own && *l == link.uid) {
+		result.push((own.to_string(), link.uid));
+	    }
+	}
+    }
+    result
+}
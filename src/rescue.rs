@@ -0,0 +1,335 @@
+// Recovering what can be recovered from damaged calendar files: line breaks within characters and
+// lost folding indentation, bytes that are not UTF-8, components that are never ended (or ended
+// twice), and components that do not parse even then.  Every repair is reported.
+
+/// Components that only occur directly in a calendar; all others (VALARM, STANDARD, ...) occur
+/// in these, and contain no components themselves
+const TOP_LEVEL: &[&str] = &["VEVENT", "VTODO", "VJOURNAL", "VFREEBUSY", "VTIMEZONE", "VAVAILABILITY"];
+
+#[derive(Default)]
+pub struct Report {
+    /// Line breaks removed from within UTF-8 sequences
+    pub sequences_joined: usize,
+    /// Bytes that are not UTF-8, read as Latin-1
+    pub invalid_bytes: usize,
+    /// Lines that continue the previous one but lost their indentation
+    pub lines_joined: usize,
+    /// Lines that were neither content lines nor continuations, or stray END lines
+    pub lines_dropped: usize,
+    /// Components that were not ended, and ended where the next one began
+    pub components_closed: usize,
+    /// What was dropped, and why, with line numbers
+    pub dropped: Vec<String>,
+    /// Recovered components, by kind
+    pub recovered: Vec<(String, usize)>,
+}
+
+impl Report {
+    /// One-line summary of the repairs
+    pub fn summary(&self) -> String {
+	let counts = [
+	    (self.sequences_joined, "split UTF-8 sequence re-joined", "split UTF-8 sequences re-joined"),
+	    (self.invalid_bytes, "invalid byte read as Latin-1", "invalid bytes read as Latin-1"),
+	    (self.lines_joined, "broken fold repaired", "broken folds repaired"),
+	    (self.lines_dropped, "garbled line dropped", "garbled lines dropped"),
+	    (self.components_closed, "unterminated component closed", "unterminated components closed"),
+	    (self.dropped.len(), "component dropped", "components dropped"),
+	];
+	let mut parts: Vec<String> = self.recovered.iter().map(|(kind, n)| format!("{} {} recovered", n, kind)).collect();
+	parts.extend(counts.iter().filter(|(n, _, _)| *n > 0).map(|(n, one, many)| format!("{} {}", n, if *n == 1 { one } else { many })));
+	match parts.is_empty() {
+	    true  => "nothing to recover".to_string(),
+	    false => parts.join(", "),
+	}
+    }
+}
+
+/// Repaired calendar text, and what was repaired
+pub fn rescue(input: &[u8]) -> (String, Report) {
+    let mut report = Report::default();
+    let text = decode(input, &mut report);
+    let lines = content_lines(&text, &mut report);
+    let text = structure(lines, &mut report);
+    (text, report)
+}
+
+/// Length of the UTF-8 sequence that a byte starts, if it is a lead byte
+fn sequence_length(byte: u8) -> Option<usize> {
+    match byte {
+	0xc2..=0xdf => Some(2),
+	0xe0..=0xef => Some(3),
+	0xf0..=0xf4 => Some(4),
+	_           => None,
+    }
+}
+
+/// Text of the input, without line breaks (and fold indentation) within UTF-8 sequences, and
+/// reading anything else that is not UTF-8 as Latin-1
+fn decode(input: &[u8], report: &mut Report) -> String {
+    let line_break = |rest: &[u8]| match rest {
+	[b'\r', b'\n', b' ' | b'\t', ..] => 3,
+	[b'\r', b'\n', ..]               => 2,
+	[b'\n', b' ' | b'\t', ..]        => 2,
+	[b'\n', ..]                      => 1,
+	_                                => 0,
+    };
+    let mut result = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+	if input[i] < 0x80 {
+	    result.push(input[i] as char);
+	    i += 1;
+	    continue;
+	}
+	// Collect the continuation bytes, skipping line breaks between them
+	let mut sequence = vec![input[i]];
+	let mut j = i + 1;
+	let mut breaks = 0;
+	while sequence.len() < sequence_length(input[i]).unwrap_or(1) && j < input.len() {
+	    let skip = line_break(&input[j..]);
+	    match input.get(j + skip) {
+		Some(byte) if (0x80..0xc0).contains(byte) => {
+		    breaks += usize::from(skip > 0);
+		    sequence.push(*byte);
+		    j += skip + 1;
+		},
+		_ => break,
+	    }
+	}
+	match std::str::from_utf8(&sequence) {
+	    Ok(character) => {
+		result.push_str(character);
+		report.sequences_joined += breaks;
+		i = j;
+	    },
+	    Err(_) => {
+		result.push(input[i] as char);
+		report.invalid_bytes += 1;
+		i += 1;
+	    },
+	}
+    }
+    result
+}
+
+/// Whether a line starts like a content line, "NAME:" or "NAME;"
+fn is_content_line(line: &str) -> bool {
+    match line.find([':', ';']) {
+	Some(end) => end > 0 && line[..end].chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+	None      => false,
+    }
+}
+
+/// Unfolded content lines, with the number of the physical line that each starts on; lines that
+/// do not look like content lines continue the previous one
+fn content_lines(text: &str, report: &mut Report) -> Vec<(usize, String)> {
+    let mut result: Vec<(usize, String)> = vec![];
+    for (number, line) in text.lines().enumerate() {
+	let line = line.trim_end_matches('\r');
+	if let Some(continuation) = line.strip_prefix([' ', '\t']) {
+	    match result.last_mut() {
+		Some((_, last)) => last.push_str(continuation),
+		None            => report.lines_dropped += 1,
+	    }
+	} else if is_content_line(line) {
+	    result.push((number + 1, line.to_string()));
+	} else if line.trim().is_empty() {
+	    continue;
+	} else {
+	    match result.last_mut() {
+		Some((_, last)) => { last.push_str(line); report.lines_joined += 1; },
+		None            => report.lines_dropped += 1,
+	    }
+	}
+    }
+    result
+}
+
+/// Component name of a BEGIN or END line
+fn marker(line: &str, keyword: &str) -> Option<String> {
+    let (name, value) = line.split_once(':')?;
+    name.eq_ignore_ascii_case(keyword).then(|| value.trim().to_uppercase())
+}
+
+/// A component with its lines, nested ones included
+struct Block {
+    kind: String,
+    line: usize,
+    lines: Vec<String>,
+}
+
+/// Calendar text with properly nested components, each of which parses; components that do not
+/// parse are dropped
+fn structure(lines: Vec<(usize, String)>, report: &mut Report) -> String {
+    let mut properties = vec![];
+    let mut blocks: Vec<Block> = vec![];
+    // Open components below VCALENDAR: kind and line number
+    let mut open: Vec<(String, usize)> = vec![];
+    let mut current: Vec<String> = vec![];
+    let close = |open: &mut Vec<(String, usize)>, current: &mut Vec<String>, blocks: &mut Vec<Block>| {
+	let (kind, line) = open.pop().unwrap();
+	current.push(format!("END:{}", kind));
+	if open.is_empty() {
+	    blocks.push(Block { kind, line, lines: std::mem::take(current) });
+	}
+    };
+    for (number, line) in lines {
+	if let Some(kind) = marker(&line, "BEGIN") {
+	    if kind == "VCALENDAR" {
+		// Concatenated calendars continue the first one
+		while !open.is_empty() {
+		    report.components_closed += 1;
+		    close(&mut open, &mut current, &mut blocks);
+		}
+		continue;
+	    }
+	    // A component ends unended ones that cannot contain it
+	    let top_level = TOP_LEVEL.contains(&kind.as_str());
+	    while open.last().is_some_and(|(parent, _)| top_level || !TOP_LEVEL.contains(&parent.as_str())) {
+		report.components_closed += 1;
+		close(&mut open, &mut current, &mut blocks);
+	    }
+	    current.push(format!("BEGIN:{}", kind));
+	    open.push((kind, number));
+	} else if let Some(kind) = marker(&line, "END") {
+	    if kind == "VCALENDAR" {
+		continue;
+	    }
+	    match open.iter().rposition(|(open_kind, _)| *open_kind == kind) {
+		Some(index) => {
+		    while open.len() > index + 1 {
+			report.components_closed += 1;
+			close(&mut open, &mut current, &mut blocks);
+		    }
+		    close(&mut open, &mut current, &mut blocks);
+		},
+		None => report.lines_dropped += 1,
+	    }
+	} else if open.is_empty() {
+	    properties.push(line);
+	} else {
+	    current.push(line);
+	}
+    }
+    while !open.is_empty() {
+	report.components_closed += 1;
+	close(&mut open, &mut current, &mut blocks);
+    }
+
+    let wrap = |properties: &[String], lines: &[String]| {
+	let mut text = "BEGIN:VCALENDAR\r\n".to_string();
+	for line in properties.iter().chain(lines) {
+	    text += line;
+	    text += "\r\n";
+	}
+	text + "END:VCALENDAR\r\n"
+    };
+    let parses = |text: &str| icalendar::parser::read_calendar(&icalendar::parser::unfold(text)).is_ok();
+    // Calendar properties that do not parse are dropped one by one
+    let mut kept_properties = vec![];
+    for property in properties {
+	if parses(&wrap(std::slice::from_ref(&property), &[])) {
+	    kept_properties.push(property);
+	} else {
+	    report.dropped.push(format!("calendar property '{}': cannot parse", property));
+	}
+    }
+    let mut kept = vec![];
+    for block in blocks {
+	let uid = block.lines.iter().find_map(|line| line.strip_prefix("UID:")).map_or(String::new(), |uid| format!(" (UID {})", uid));
+	if !parses(&wrap(&[], &block.lines)) {
+	    report.dropped.push(format!("line {}: {}{}: cannot parse", block.line, block.kind, uid));
+	} else if block.kind == "VEVENT" && !block.lines.iter().any(|line| is_property(line, "DTSTART")) {
+	    report.dropped.push(format!("line {}: {}{}: no DTSTART", block.line, block.kind, uid));
+	} else {
+	    match report.recovered.iter_mut().find(|(kind, _)| *kind == block.kind) {
+		Some((_, n)) => *n += 1,
+		None         => report.recovered.push((block.kind.clone(), 1)),
+	    }
+	    kept.extend(block.lines);
+	}
+    }
+    wrap(&kept_properties, &kept)
+}
+
+fn is_property(line: &str, name: &str) -> bool {
+    line.get(..name.len()).is_some_and(|start| start.eq_ignore_ascii_case(name)) && line[name.len()..].starts_with([':', ';'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(uid: &str, summary: &[u8]) -> Vec<u8> {
+	let mut lines = format!("BEGIN:VEVENT\r\nUID:{}\r\nDTSTAMP:20250101T000000Z\r\nDTSTART:20250101T100000Z\r\nSUMMARY:", uid).into_bytes();
+	lines.extend_from_slice(summary);
+	lines.extend_from_slice(b"\r\nEND:VEVENT\r\n");
+	lines
+    }
+
+    fn calendar(body: &[u8]) -> Vec<u8> {
+	[b"BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:test\r\n".as_slice(), body, b"END:VCALENDAR\r\n"].concat()
+    }
+
+    #[test]
+    fn joins_utf8_sequence_split_across_fold() {
+	// "Grüße", folded between the two bytes of "ü"
+	let (text, report) = rescue(&calendar(&event("a", b"Gr\xc3\r\n \xbc\xc3\x9fe")));
+	assert!(text.contains("SUMMARY:Grüße\r\n"), "{}", text);
+	assert_eq!(report.sequences_joined, 1);
+	assert_eq!(report.invalid_bytes, 0);
+    }
+
+    #[test]
+    fn reads_invalid_bytes_as_latin1() {
+	let (text, report) = rescue(&calendar(&event("a", b"caf\xe9")));
+	assert!(text.contains("SUMMARY:café\r\n"), "{}", text);
+	assert_eq!(report.invalid_bytes, 1);
+    }
+
+    #[test]
+    fn repairs_lost_fold_indentation() {
+	let (text, report) = rescue(&calendar(&event("a", b"Team\r\nmeeting")));
+	assert!(text.contains("SUMMARY:Teammeeting\r\n"), "{}", text);
+	assert_eq!(report.lines_joined, 1);
+    }
+
+    #[test]
+    fn closes_unterminated_vevent() {
+	let mut first = event("a", b"First");
+	first.truncate(first.len() - b"END:VEVENT\r\n".len());
+	let (text, report) = rescue(&calendar(&[first, event("b", b"Second")].concat()));
+	assert_eq!(report.components_closed, 1);
+	assert_eq!(report.recovered, vec![("VEVENT".to_string(), 2)]);
+	assert_eq!(text.matches("BEGIN:VEVENT").count(), 2);
+	assert_eq!(text.matches("END:VEVENT").count(), 2);
+	assert!(text.find("END:VEVENT").unwrap() < text.find("UID:b").unwrap(), "{}", text);
+    }
+
+    #[test]
+    fn closes_vevent_unterminated_at_end_of_file() {
+	let mut body = event("a", b"Only");
+	body.truncate(body.len() - b"END:VEVENT\r\n".len());
+	let (text, report) = rescue(&body);
+	assert_eq!(report.components_closed, 1);
+	assert!(text.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"), "{}", text);
+    }
+
+    #[test]
+    fn keeps_alarm_inside_event() {
+	let body = b"BEGIN:VEVENT\r\nUID:a\r\nDTSTART:20250101T100000Z\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT5M\r\nEND:VALARM\r\nEND:VEVENT\r\n";
+	let (text, report) = rescue(&calendar(body));
+	assert_eq!(report.components_closed, 0);
+	assert_eq!(report.recovered, vec![("VEVENT".to_string(), 1)]);
+	assert!(text.contains("BEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT5M\r\nEND:VALARM\r\nEND:VEVENT\r\n"), "{}", text);
+    }
+
+    #[test]
+    fn drops_event_without_dtstart() {
+	let body = b"BEGIN:VEVENT\r\nUID:a\r\nSUMMARY:No start\r\nEND:VEVENT\r\n";
+	let (text, report) = rescue(&calendar(body));
+	assert!(!text.contains("BEGIN:VEVENT"), "{}", text);
+	assert_eq!(report.dropped.len(), 1);
+	assert!(report.dropped[0].contains("no DTSTART"), "{:?}", report.dropped);
+    }
+}
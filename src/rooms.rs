@@ -0,0 +1,135 @@
+// Rooms and other resources that events book: values of RESOURCES, and attendees with CUTYPE=ROOM
+// or CUTYPE=RESOURCE, as in Exchange exports.  Rooms that declined an invitation are not booked.
+
+use chrono::{DateTime, Duration, Utc};
+use icalendar::{Component, Event};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::recurrence;
+
+/// A room booked by an event: its name, and its address if it was invited as an attendee
+pub struct Room {
+    pub name: String,
+    pub address: Option<String>,
+}
+
+impl Room {
+    /// Whether `room` names this room, by name or by address, ignoring case
+    pub fn is(&self, room: &str) -> bool {
+	self.name.eq_ignore_ascii_case(room) || self.address.as_deref().is_some_and(|address| address.eq_ignore_ascii_case(room))
+    }
+}
+
+/// The rooms an event books, in order of appearance
+pub fn booked(event: &Event) -> Vec<Room> {
+    let mut result: Vec<Room> = vec![];
+    let properties = || event.properties().values().chain(event.multi_properties().values().flatten());
+    for property in properties().filter(|p| p.key() == "RESOURCES") {
+	for name in property.value().split(',').map(str::trim).filter(|name| !name.is_empty()) {
+	    result.push(Room { name: name.to_string(), address: None });
+	}
+    }
+    for attendee in properties().filter(|p| p.key() == "ATTENDEE") {
+	let param = |key: &str| attendee.params().get(key).map(|p| p.value().to_uppercase());
+	if !matches!(param("CUTYPE").as_deref(), Some("ROOM" | "RESOURCE")) || param("PARTSTAT").as_deref() == Some("DECLINED") {
+	    continue;
+	}
+	let address = crate::mailto_address(attendee).unwrap_or(attendee.value()).to_string();
+	let name = attendee.params().get("CN").map_or(address.clone(), |cn| cn.value().to_string());
+	// The same room may be listed both ways
+	match result.iter_mut().find(|room| room.is(&name)) {
+	    Some(room) => room.address = Some(address),
+	    None       => result.push(Room { name, address: Some(address) }),
+	}
+    }
+    let mut seen = HashSet::new();
+    result.retain(|room| seen.insert(room.name.to_lowercase()));
+    result
+}
+
+/// One occurrence of an event that books a room
+struct Booking<'a> {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    event: &'a Event,
+}
+
+/// Two bookings of one room that overlap
+pub struct Conflict<'a> {
+    pub first: &'a Event,
+    pub second: &'a Event,
+    /// Start of the overlap
+    pub start: DateTime<Utc>,
+}
+
+/// How one room is used within a window
+pub struct Usage<'a> {
+    /// Name as first seen
+    pub name: String,
+    pub bookings: usize,
+    /// Time during which the room is booked at all; overlapping bookings count once
+    pub occupied: Duration,
+    pub conflicts: Vec<Conflict<'a>>,
+}
+
+/// Usage of all rooms booked by (occurrences of) events in the window from `from` to `to`, by
+/// lower-case name; cancelled events and occurrences that an override replaces do not count
+pub fn usage<'a>(events: &[&'a Event], from: DateTime<Utc>, to: DateTime<Utc>) -> BTreeMap<String, Usage<'a>> {
+    let overridden: HashSet<(&str, DateTime<Utc>)> = events.iter()
+	.filter_map(|event| Some((event.get_uid()?, recurrence::property_date_time(event.properties().get("RECURRENCE-ID")?)?.with_timezone(&Utc))))
+	.collect();
+    // Name and bookings of each room
+    let mut bookings: BTreeMap<String, (String, Vec<Booking>)> = BTreeMap::new();
+    for &event in events {
+	let rooms = booked(event);
+	if rooms.is_empty() || event.property_value("STATUS") == Some("CANCELLED") {
+	    continue;
+	}
+	let is_override = event.properties().contains_key("RECURRENCE-ID");
+	for occurrence in recurrence::occurrences(event, from, Some(to), u16::MAX) {
+	    let start = occurrence.start.with_timezone(&Utc);
+	    if !is_override && event.get_uid().is_some_and(|uid| overridden.contains(&(uid, start))) {
+		continue;
+	    }
+	    // Only the part within the window counts
+	    let (start, end) = (start.max(from), occurrence.end().with_timezone(&Utc).min(to));
+	    if start >= end {
+		continue;
+	    }
+	    for room in &rooms {
+		let entry = bookings.entry(room.name.to_lowercase()).or_insert_with(|| (room.name.clone(), vec![]));
+		entry.1.push(Booking { start, end, event });
+	    }
+	}
+    }
+    let mut result = BTreeMap::new();
+    for (key, (name, mut times)) in bookings {
+	times.sort_by_key(|booking| (booking.start, booking.end));
+	let mut usage = Usage { name, bookings: times.len(), occupied: Duration::zero(), conflicts: vec![] };
+	// The booking that ends last so far, and the start of the current stretch of occupied time
+	let mut latest: Option<(DateTime<Utc>, &Event)> = None;
+	let mut stretch_start = from;
+	for &Booking { start, end, event } in &times {
+	    match latest {
+		Some((latest_end, latest_event)) if start < latest_end => {
+		    usage.conflicts.push(Conflict { first: latest_event, second: event, start });
+		    if end > latest_end {
+			latest = Some((end, event));
+		    }
+		},
+		_ => {
+		    if let Some((latest_end, _)) = latest {
+			usage.occupied += latest_end - stretch_start;
+		    }
+		    stretch_start = start;
+		    latest = Some((end, event));
+		},
+	    }
+	}
+	if let Some((latest_end, _)) = latest {
+	    usage.occupied += latest_end - stretch_start;
+	}
+	result.insert(key, usage);
+    }
+    result
+}
@@ -0,0 +1,137 @@
+// Rotas: duty slots assigned to people in turn, e.g. for on-call or cleaning schedules
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Utc, Weekday};
+use icalendar::{Component, Event, Property};
+
+use crate::template::Frequency;
+
+/// Placeholder for the person's name in summaries
+pub const NAME_PLACEHOLDER: &str = "{name}";
+
+/// Recurring duty times within a period, e.g. "Mon 09:00-17:00" or "Mon-Fri 18:00-08:00"
+#[derive(Clone)]
+pub struct Slot {
+    /// Days on which the slot starts; all days if empty
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    /// Ends on the next day if not after `start`
+    end: NaiveTime,
+}
+
+fn parse_weekday(text: &str) -> Result<Weekday, String> {
+    text.parse::<Weekday>().map_err(|_| format!("unknown day '{}'", text))
+}
+
+/// Parse "[DAYS ]HH:MM-HH:MM", where DAYS is a list of days and day ranges, e.g. "Mon,Wed-Fri"
+pub fn parse_slot(arg: &str) -> Result<Slot, String> {
+    let (days_text, hours) = match arg.trim().rsplit_once(' ') {
+	Some((days, hours)) => (days.trim(), hours),
+	None                => ("", arg.trim()),
+    };
+    let mut days = vec![];
+    for part in days_text.split(',').filter(|part| !part.trim().is_empty()) {
+	match part.split_once('-') {
+	    Some((first, last)) => {
+		let (mut day, last) = (parse_weekday(first.trim())?, parse_weekday(last.trim())?);
+		days.push(day);
+		while day != last {
+		    day = day.succ();
+		    days.push(day);
+		}
+	    },
+	    None => days.push(parse_weekday(part.trim())?),
+	}
+    }
+    let (start, end) = hours.split_once('-').ok_or_else(|| format!("expected e.g. 'Mon 09:00-17:00', not '{}'", arg))?;
+    let time = |text: &str| NaiveTime::parse_from_str(text.trim(), "%H:%M").map_err(|_| format!("cannot parse time '{}'", text));
+    Ok(Slot { days, start: time(start)?, end: time(end)? })
+}
+
+/// Someone on the rota: "Name <address>", a bare address, or a bare name
+pub struct Person {
+    pub name: String,
+    pub address: Option<String>,
+}
+
+impl Person {
+    /// Parse a person; `domain` completes bare names to addresses
+    pub fn parse(text: &str, domain: Option<&str>) -> Person {
+	let text = text.trim();
+	if let Some((name, rest)) = text.split_once('<') {
+	    return Person { name: name.trim().to_string(), address: Some(rest.trim_end_matches('>').trim().to_string()) };
+	}
+	let address = match domain {
+	    _ if text.contains('@') => Some(text.to_string()),
+	    Some(domain)            => Some(format!("{}@{}", text.to_lowercase(), domain)),
+	    None                    => None,
+	};
+	Person { name: text.to_string(), address }
+    }
+}
+
+/// What to generate
+pub struct Rota<'a> {
+    pub people: &'a [Person],
+    /// How often the duty passes on to the next person
+    pub pattern: Frequency,
+    pub slots: &'a [Slot],
+    pub from: NaiveDate,
+    /// Number of periods
+    pub periods: u32,
+    /// Summary, with NAME_PLACEHOLDER for the person on duty
+    pub summary: &'a str,
+    /// Time zone of the slots (floating time if None)
+    pub tzid: Option<&'a str>,
+}
+
+/// Start of the period that contains `date`: the day itself, its week's Monday, or the first day
+/// of its month or year
+fn period_start(pattern: Frequency, date: NaiveDate) -> NaiveDate {
+    match pattern {
+	Frequency::Daily   => date,
+	Frequency::Weekly  => date - Duration::days(date.weekday().num_days_from_monday().into()),
+	Frequency::Monthly => date.with_day(1).unwrap(),
+	Frequency::Yearly  => date.with_ordinal(1).unwrap(),
+    }
+}
+
+/// One event per slot, starting on `from` and assigning the people in turn, one per period
+pub fn generate(rota: &Rota) -> Result<Vec<Event>, String> {
+    if rota.people.is_empty() {
+	return Err("nobody is on the rota".to_string());
+    }
+    let first = period_start(rota.pattern, rota.from).and_hms_opt(0, 0, 0).unwrap();
+    let mut events = vec![];
+    for period in 0..rota.periods {
+	let person = &rota.people[period as usize % rota.people.len()];
+	let (Some(start), Some(end)) = (rota.pattern.nth(first, period), rota.pattern.nth(first, period + 1)) else {
+	    return Err(format!("period {} is out of range", period + 1));
+	};
+	for day in start.date().iter_days().take_while(|day| *day < end.date()).filter(|day| *day >= rota.from) {
+	    for slot in rota.slots.iter().filter(|slot| slot.days.is_empty() || slot.days.contains(&day.weekday())) {
+		let slot_start = day.and_time(slot.start);
+		let slot_end = if slot.end > slot.start { day.and_time(slot.end) } else { (day + Duration::days(1)).and_time(slot.end) };
+		let mut event = Event::new();
+		let summary = rota.summary.replace(NAME_PLACEHOLDER, &person.name);
+		event.uid(&format!("{:016x}@icalm", crate::fnv1a(&format!("{}\u{1f}{}", summary, slot_start))));
+		event.timestamp(Utc::now());
+		for (key, time) in [("DTSTART", slot_start), ("DTEND", slot_end)] {
+		    let mut property = Property::new(key, time.format("%Y%m%dT%H%M%S").to_string());
+		    if let Some(tzid) = rota.tzid {
+			property.add_parameter("TZID", tzid);
+		    }
+		    event.append_property(property);
+		}
+		event.summary(&summary);
+		if let Some(ref address) = person.address {
+		    let mut attendee = Property::new("ATTENDEE", format!("mailto:{}", address));
+		    attendee.add_parameter("CN", &person.name);
+		    attendee.add_parameter("ROLE", "REQ-PARTICIPANT");
+		    event.append_multi_property(attendee);
+		}
+		events.push(event.done());
+	    }
+	}
+    }
+    Ok(events)
+}
@@ -0,0 +1,58 @@
+// Stopping icalm (SIGINT, SIGTERM) while it replaces files: the signal is deferred until all files
+// are replaced, or until the temporary files are removed again, so that no published calendar is
+// ever left half-written
+
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Signal received while deferring (0 for none)
+static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+#[cfg(unix)]
+const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+#[cfg(unix)]
+extern "C" fn remember(signal: libc::c_int) {
+    RECEIVED.store(signal, Ordering::SeqCst);
+}
+
+/// While alive, SIGINT and SIGTERM are only recorded; afterwards, they act as before
+pub struct Deferred {
+    #[cfg(unix)]
+    previous: [libc::sighandler_t; 2],
+}
+
+pub fn defer() -> Deferred {
+    #[cfg(unix)]
+    {
+	let handler = remember as extern "C" fn(libc::c_int) as libc::sighandler_t;
+	// SAFETY: the handler only stores to an atomic, which is async-signal-safe
+	Deferred { previous: SIGNALS.map(|signal| unsafe { libc::signal(signal, handler) }) }
+    }
+    #[cfg(not(unix))]
+    Deferred {}
+}
+
+impl Deferred {
+    /// The signal received so far, if any
+    pub fn received(&self) -> Option<i32> {
+	match RECEIVED.load(Ordering::SeqCst) {
+	    0      => None,
+	    signal => Some(signal),
+	}
+    }
+}
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+	#[cfg(unix)]
+	for (signal, previous) in SIGNALS.iter().zip(self.previous) {
+	    // SAFETY: restores the handler that was installed before
+	    unsafe { libc::signal(*signal, previous); }
+	}
+    }
+}
+
+/// Exit status after being stopped by a signal, as shells report it (130 for SIGINT, 143 for SIGTERM)
+pub fn exit_status(signal: i32) -> i32 {
+    128 + signal
+}
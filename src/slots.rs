@@ -0,0 +1,169 @@
+// Finding meeting slots in which several people are free
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use icalendar::{Calendar, CalendarComponent, Component, Event, EventLike};
+use std::collections::{HashMap, HashSet};
+
+use crate::{holidays::Holidays, recurrence};
+
+/// Maximal number of occurrences of a single event to consider
+const MAX_OCCURRENCES: u16 = 10000;
+
+/// Someone's busy times
+pub struct Person {
+    pub name: String,
+    busy: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl Person {
+    /// Busy times from a calendar: non-transparent, non-cancelled events and VFREEBUSY periods
+    pub fn new(name: String, calendar: &Calendar, from: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+	let mut busy = vec![];
+	// Occurrences that overrides (RECURRENCE-ID) move elsewhere
+	let mut overridden: HashMap<&str, HashSet<DateTime<Utc>>> = HashMap::new();
+	for event in calendar.components.iter().filter_map(|c| c.as_event()) {
+	    if let (Some(uid), Some(recurrence_id)) = (event.get_uid(), event.properties().get("RECURRENCE-ID")) {
+		if let Some(time) = recurrence::property_date_time(recurrence_id) {
+		    overridden.entry(uid).or_default().insert(time.with_timezone(&Utc));
+		}
+	    }
+	}
+	for component in &calendar.components {
+	    match component {
+		CalendarComponent::Event(event) if blocks_time(event) => {
+		    let moved = event.get_uid().filter(|_| !event.properties().contains_key("RECURRENCE-ID")).and_then(|uid| overridden.get(uid));
+		    for occurrence in recurrence::occurrences(event, from, Some(until), MAX_OCCURRENCES) {
+			let start = occurrence.start.with_timezone(&Utc);
+			if !moved.is_some_and(|moved| moved.contains(&start)) {
+			    busy.push((start, occurrence.end().with_timezone(&Utc)));
+			}
+		    }
+		},
+		CalendarComponent::Other(other) if other.component_kind() == "VFREEBUSY" => {
+		    busy.extend(free_busy_periods(other));
+		},
+		_ => {},
+	    }
+	}
+	Person { name, busy }
+    }
+
+    pub fn is_free(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+	!self.busy.iter().any(|(busy_start, busy_end)| *busy_start < end && start < *busy_end)
+    }
+}
+
+/// Does the event block time?  Not if it is TRANSPARENT or CANCELLED.
+fn blocks_time(event: &Event) -> bool {
+    !event.property_value("TRANSP").is_some_and(|t| t.eq_ignore_ascii_case("TRANSPARENT"))
+	&& !event.property_value("STATUS").is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED"))
+}
+
+/// Busy periods of a VFREEBUSY component: "start/end" or "start/duration", in UTC
+fn free_busy_periods<C: Component>(component: &C) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let properties = component.multi_properties().get("FREEBUSY").into_iter().flatten()
+	.chain(component.properties().get("FREEBUSY"));
+    let mut result = vec![];
+    for property in properties {
+	if property.params().get("FBTYPE").is_some_and(|t| t.value().eq_ignore_ascii_case("FREE")) {
+	    continue;
+	}
+	for period in property.value().split(',') {
+	    let Some((start, end)) = period.trim().split_once('/') else {
+		continue;
+	    };
+	    let Some(start) = recurrence::parse_date_time_value(start, rrule::Tz::UTC) else {
+		continue;
+	    };
+	    let end = recurrence::parse_duration(end).map(|duration| start + duration)
+		.or_else(|| recurrence::parse_date_time_value(end, rrule::Tz::UTC));
+	    if let Some(end) = end {
+		result.push((start.with_timezone(&Utc), end.with_timezone(&Utc)));
+	    }
+	}
+    }
+    result
+}
+
+// --------------------------------------------------------------------------------
+
+/// Where and how long to look for slots
+pub struct Search {
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub duration: Duration,
+    /// Candidate slots start at multiples of this, from the start of the working day
+    pub step: Duration,
+    /// Working hours, in local time
+    pub day_start: NaiveTime,
+    pub day_end: NaiveTime,
+    pub weekends: bool,
+    /// Days without working hours
+    pub holidays: Holidays,
+}
+
+/// A candidate meeting time and who can attend
+pub struct Slot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub free: Vec<String>,
+    pub busy: Vec<String>,
+}
+
+/// Slots in which at least `quorum` people are free, those with most people free first, then
+/// earliest first
+pub fn find_slots(people: &[Person], search: &Search, quorum: usize) -> Vec<Slot> {
+    let mut slots = vec![];
+    let last_day = search.until.with_timezone(&Local).date_naive();
+    for day in search.from.with_timezone(&Local).date_naive().iter_days().take_while(|day| *day <= last_day) {
+	if (!search.weekends && matches!(day.weekday(), Weekday::Sat | Weekday::Sun)) || search.holidays.contains(day) {
+	    continue;
+	}
+	let (Some(day_start), Some(day_end)) = (local_time(day, search.day_start), local_time(day, search.day_end)) else {
+	    continue;
+	};
+	let mut start = day_start;
+	while start + search.duration <= day_end.min(search.until) {
+	    let end = start + search.duration;
+	    if start >= search.from {
+		let (free, busy): (Vec<&Person>, Vec<&Person>) = people.iter().partition(|person| person.is_free(start, end));
+		if free.len() >= quorum {
+		    slots.push(Slot {
+			start,
+			end,
+			free: free.iter().map(|person| person.name.clone()).collect(),
+			busy: busy.iter().map(|person| person.name.clone()).collect(),
+		    });
+		}
+	    }
+	    start += search.step;
+	}
+    }
+    slots.sort_by_key(|slot| (std::cmp::Reverse(slot.free.len()), slot.start));
+    slots
+}
+
+/// A local wall-clock time on a given day, as UTC; times in DST gaps move forward
+pub fn local_time(day: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+    let naive = day.and_time(time);
+    Local.from_local_datetime(&naive).earliest()
+	.or_else(|| Local.from_local_datetime(&(naive + Duration::hours(1))).earliest())
+	.map(|t| t.with_timezone(&Utc))
+}
+
+/// A tentative event proposing a slot, e.g. for a poll
+pub fn proposal(slot: &Slot, summary: &str) -> Event {
+    let mut event = Event::new();
+    event.uid(&format!("{:016x}@icalm", crate::fnv1a(&format!("{}\u{1f}{}", summary, slot.start.to_rfc3339()))));
+    event.timestamp(Utc::now());
+    event.starts(slot.start);
+    event.ends(slot.end);
+    event.summary(summary);
+    event.status(icalendar::EventStatus::Tentative);
+    let mut description = format!("Free: {}", slot.free.join(", "));
+    if !slot.busy.is_empty() {
+	description += &format!("\nBusy: {}", slot.busy.join(", "));
+    }
+    event.description(&description);
+    event.done()
+}
@@ -0,0 +1,39 @@
+// Snapshots of published outputs, for undoing a mistaken run: copies of the last versions written,
+// named by the time of writing, so that they sort by age
+
+use chrono::Utc;
+use std::{fs, io, path::{Path, PathBuf}};
+
+/// Default directory for the snapshots of an output file or URL: next to the file, or else below
+/// the home directory
+pub fn directory(target: &str) -> PathBuf {
+    if !crate::remote::is_url(target) {
+	return PathBuf::from(format!("{}.snapshots", target));
+    }
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    Path::new(&home).join(".icalm-snapshots").join(crate::remote::file_name(target))
+}
+
+/// Snapshots in `directory`, oldest first
+pub fn list(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut result: Vec<PathBuf> = match fs::read_dir(directory) {
+	Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+	    .filter(|path| path.extension().is_some_and(|extension| extension == "ics"))
+	    .collect(),
+	Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+	Err(e) => return Err(e),
+    };
+    result.sort();
+    Ok(result)
+}
+
+/// Keep `text` as the newest snapshot, and remove all but the newest `keep` ones
+pub fn save(directory: &Path, text: &str, keep: usize) -> io::Result<()> {
+    fs::create_dir_all(directory)?;
+    fs::write(directory.join(format!("{}.ics", Utc::now().format("%Y%m%dT%H%M%S%.3fZ"))), text)?;
+    let snapshots = list(directory)?;
+    for old in &snapshots[..snapshots.len().saturating_sub(keep)] {
+	fs::remove_file(old)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+// Stamping out events from a template event, e.g. for shift plans and rosters
+
+use chrono::{Duration, Local, Months, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use clap::ValueEnum;
+use icalendar::{Component, Event, Property};
+
+/// Properties of the template that do not carry over to the generated events
+const TEMPLATE_ONLY_PROPERTIES: &[&str] = &["UID", "DTSTAMP", "SEQUENCE", "RECURRENCE-ID", "RRULE", "RDATE", "EXDATE", "CREATED", "LAST-MODIFIED"];
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    /// The `index`th repetition of a wall-clock time
+    pub fn nth(self, start: NaiveDateTime, index: u32) -> Option<NaiveDateTime> {
+	match self {
+	    Frequency::Daily   => start.checked_add_signed(Duration::days(index.into())),
+	    Frequency::Weekly  => start.checked_add_signed(Duration::weeks(index.into())),
+	    Frequency::Monthly => start.checked_add_months(Months::new(index)),
+	    Frequency::Yearly  => start.checked_add_months(Months::new(index.checked_mul(12)?)),
+	}
+    }
+}
+
+/// Wall-clock value of a DATE or DATE-TIME property (UTC ones in UTC)
+fn naive_value(property: &Property) -> Option<NaiveDateTime> {
+    let value = property.value().trim().trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()
+	.or_else(|| NaiveDate::parse_from_str(value, "%Y%m%d").ok()?.and_hms_opt(0, 0, 0))
+}
+
+/// Same property, same parameters, new wall-clock value
+fn with_naive_value(property: &Property, time: NaiveDateTime) -> Property {
+    let value = if crate::recurrence::is_date_value(property) {
+	time.format("%Y%m%d").to_string()
+    } else if property.value().trim().ends_with('Z') {
+	time.format("%Y%m%dT%H%M%SZ").to_string()
+    } else {
+	time.format("%Y%m%dT%H%M%S").to_string()
+    };
+    let mut result = Property::new(property.key(), value);
+    for param in property.params().values() {
+	result.append_parameter(param.clone());
+    }
+    result
+}
+
+/// Copy of the template event starting at `at`: wall-clock time in the template's time zone
+/// (local time if the template uses UTC), with DTEND moved along and a UID derived from the
+/// template and the start
+pub fn stamp(template: &Event, at: NaiveDateTime) -> Result<Event, String> {
+    let dtstart = template.properties().get("DTSTART").ok_or("the template has no DTSTART")?;
+    let original = naive_value(dtstart).ok_or_else(|| format!("cannot parse the template's DTSTART '{}'", dtstart.value()))?;
+    let start = if dtstart.value().trim().ends_with('Z') {
+	Local.from_local_datetime(&at).earliest()
+	    .ok_or_else(|| format!("{} does not exist in the local time zone", at))?
+	    .with_timezone(&Utc).naive_utc()
+    } else {
+	at
+    };
+    let shift = start - original;
+    let mut event = crate::map_own_properties(template, |property| match property.key() {
+	key if TEMPLATE_ONLY_PROPERTIES.contains(&key) => None,
+	"DTSTART" => Some(with_naive_value(property, start)),
+	"DTEND"   => Some(naive_value(property).map_or_else(|| property.clone(), |end| with_naive_value(property, end + shift))),
+	_         => Some(property.clone()),
+    });
+    let seed = template.get_uid().or(template.get_summary()).unwrap_or("");
+    event.uid(&format!("{:016x}@icalm", crate::fnv1a(&format!("{}\u{1f}{}", seed, start))));
+    event.timestamp(Utc::now());
+    Ok(event)
+}
+
+/// `count` copies of the template, the first at `at`, the others repeating with `frequency`
+pub fn expand(template: &Event, at: NaiveDateTime, frequency: Option<Frequency>, count: u32) -> Result<Vec<Event>, String> {
+    let mut events = vec![];
+    for index in 0..count {
+	let start = match frequency {
+	    Some(frequency) => frequency.nth(at, index).ok_or_else(|| format!("repetition {} is out of range", index + 1))?,
+	    None            => at,
+	};
+	events.push(stamp(template, start)?);
+    }
+    Ok(events)
+}
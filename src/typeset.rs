@@ -0,0 +1,155 @@
+// Printable schedules: weekly or monthly timetables as Typst or LaTeX source
+
+use chrono::{Datelike, NaiveDate};
+use icalendar::{Component, Event, EventLike};
+use std::collections::HashMap;
+
+use crate::{digest::{self, Digest}, recurrence::Occurrence};
+
+/// Placeholders in templates
+pub const TITLE_PLACEHOLDER: &str = "{{title}}";
+pub const SCHEDULE_PLACEHOLDER: &str = "{{schedule}}";
+
+const TYPST_TEMPLATE: &str = "\
+#set page(paper: \"a4\", flipped: true, margin: 1.5cm)
+#set text(size: 9pt)
+
+= {{title}}
+
+{{schedule}}
+";
+
+const LATEX_TEMPLATE: &str = "\
+\\documentclass[a4paper,10pt]{article}
+\\usepackage[landscape,margin=1.5cm]{geometry}
+\\usepackage[T1]{fontenc}
+\\usepackage{tabularx}
+\\pagestyle{empty}
+\\begin{document}
+\\section*{{{title}}}
+
+{{schedule}}
+\\end{document}
+";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Markup {
+    Typst,
+    Latex,
+}
+
+impl Markup {
+    pub fn default_template(self) -> &'static str {
+	match self {
+	    Markup::Typst => TYPST_TEMPLATE,
+	    Markup::Latex => LATEX_TEMPLATE,
+	}
+    }
+
+    pub fn escape(self, text: &str) -> String {
+	let mut result = String::new();
+	for c in text.chars() {
+	    match (self, c) {
+		(Markup::Typst, '\\' | '#' | '*' | '_' | '`' | '$' | '@' | '<' | '>' | '[' | ']' | '~' | '=' | '/' | '"') => {
+		    result.push('\\');
+		    result.push(c);
+		},
+		(Markup::Latex, '&' | '%' | '$' | '#' | '_' | '{' | '}') => {
+		    result.push('\\');
+		    result.push(c);
+		},
+		(Markup::Latex, '\\') => result.push_str("\\textbackslash{}"),
+		(Markup::Latex, '~')  => result.push_str("\\textasciitilde{}"),
+		(Markup::Latex, '^')  => result.push_str("\\textasciicircum{}"),
+		(_, '\n')             => result.push(' '),
+		_                     => result.push(c),
+	    }
+	}
+	result
+    }
+
+    fn bold(self, text: &str) -> String {
+	match self {
+	    Markup::Typst => format!("*{}*", text),
+	    Markup::Latex => format!("\\textbf{{{}}}", text),
+	}
+    }
+
+    fn line_break(self) -> &'static str {
+	match self {
+	    Markup::Typst => " \\ ",
+	    Markup::Latex => " \\newline ",
+	}
+    }
+
+    /// A table with the given number of equally wide columns (or a narrow first column and a wide second one)
+    fn table(self, columns: usize, cells: &[String]) -> String {
+	match self {
+	    Markup::Typst => {
+		let widths = if columns == 2 { "(auto, 1fr)".to_string() } else { format!("({})", vec!["1fr"; columns].join(", ")) };
+		let cells: Vec<String> = cells.iter().map(|cell| format!("  [{}],\n", cell)).collect();
+		format!("#table(\n  columns: {},\n  inset: 5pt,\n{})\n", widths, cells.concat())
+	    },
+	    Markup::Latex => {
+		let spec = if columns == 2 { "|l|X|".to_string() } else { format!("|{}", "X|".repeat(columns)) };
+		let rows: Vec<String> = cells.chunks(columns).map(|row| format!("{} \\\\ \\hline\n", row.join(" & "))).collect();
+		format!("\\begin{{tabularx}}{{\\textwidth}}{{{}}}\n\\hline\n{}\\end{{tabularx}}\n", spec, rows.concat())
+	    },
+	}
+    }
+}
+
+/// Replace the placeholders of a template
+pub fn fill_template(template: &str, title: &str, schedule: &str) -> String {
+    template.replace(TITLE_PLACEHOLDER, title).replace(SCHEDULE_PLACEHOLDER, schedule)
+}
+
+/// One line per occurrence: time, summary and location
+fn describe_entries(markup: Markup, digest: &Digest, entries: &[(Occurrence, &Event)], with_location: bool) -> String {
+    let lines: Vec<String> = entries.iter()
+	.map(|(occurrence, event)| {
+	    let mut line = format!("{} {}", markup.escape(&digest::describe_time(occurrence, &digest.locale)),
+				   markup.bold(&markup.escape(event.get_summary().unwrap_or(digest.locale.no_summary()))));
+	    if let Some(location) = event.get_location().filter(|_| with_location) {
+		line += &format!(" ({})", markup.escape(location));
+	    }
+	    line
+	})
+	.collect();
+    lines.join(markup.line_break())
+}
+
+/// A table with one row per day from `first` to `last`
+pub fn week_schedule(markup: Markup, digest: &Digest, first: NaiveDate, last: NaiveDate) -> String {
+    let by_day: HashMap<NaiveDate, &Vec<(Occurrence, &Event)>> = digest.days.iter().map(|(date, entries)| (*date, entries)).collect();
+    let mut cells = vec![];
+    for date in first.iter_days().take_while(|date| *date <= last) {
+	cells.push(markup.bold(&markup.escape(&digest.locale.date(date))));
+	cells.push(by_day.get(&date).map_or_else(String::new, |entries| describe_entries(markup, digest, entries, true)));
+    }
+    markup.table(2, &cells)
+}
+
+/// A month grid, one week per row, for the month that contains `first`
+pub fn month_schedule(markup: Markup, digest: &Digest, first: NaiveDate) -> String {
+    let by_day: HashMap<NaiveDate, &Vec<(Occurrence, &Event)>> = digest.days.iter().map(|(date, entries)| (*date, entries)).collect();
+    let month_start = first.with_day(1).unwrap();
+    let grid_start = digest.locale.week_of(month_start);
+    let mut cells: Vec<String> = digest.locale.week().map(|day| markup.bold(&markup.escape(digest.locale.weekday_abbreviation(day)))).collect();
+    for date in grid_start.iter_days() {
+	if date.month() != month_start.month() && date > month_start && date == digest.locale.week_of(date) {
+	    break;
+	}
+	if date.month() != month_start.month() {
+	    cells.push(String::new());
+	    continue;
+	}
+	let mut cell = markup.bold(&date.day().to_string());
+	if let Some(entries) = by_day.get(&date) {
+	    cell += markup.line_break();
+	    cell += &describe_entries(markup, digest, entries, false);
+	}
+	cells.push(cell);
+    }
+    markup.table(7, &cells)
+}
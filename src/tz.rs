@@ -0,0 +1,691 @@
+// VTIMEZONE definitions: evaluating embedded ones and generating them from the IANA database
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, OffsetName};
+use icalendar::{Calendar, CalendarComponent, Component};
+use rrule::{RRule, RRuleSet, Tz, Unvalidated};
+use std::collections::{HashMap, HashSet};
+
+use crate::recurrence;
+
+/// Parse a UTC offset value such as "+0200" or "-053000" into seconds
+pub fn parse_utc_offset(value: &str) -> Option<i32> {
+    let (sign, digits) = match value.as_bytes().first()? {
+	b'+' => (1, &value[1..]),
+	b'-' => (-1, &value[1..]),
+	_    => return None,
+    };
+    if !(digits.len() == 4 || digits.len() == 6) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+	return None;
+    }
+    let hours: i32 = digits[0..2].parse().ok()?;
+    let minutes: i32 = digits[2..4].parse().ok()?;
+    let seconds: i32 = if digits.len() == 6 { digits[4..6].parse().ok()? } else { 0 };
+    if minutes > 59 || seconds > 59 {
+	return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Format seconds as a UTC offset value ("+0200", "-0330", "+004510")
+pub fn format_utc_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let abs = offset.abs();
+    if abs % 60 == 0 {
+	format!("{}{:02}{:02}", sign, abs / 3600, (abs / 60) % 60)
+    } else {
+	format!("{}{:02}{:02}{:02}", sign, abs / 3600, (abs / 60) % 60, abs % 60)
+    }
+}
+
+/// Offset in effect at `t` according to the IANA database
+pub fn iana_offset(tz: chrono_tz::Tz, t: DateTime<Utc>) -> i32 {
+    tz.offset_from_utc_datetime(&t.naive_utc()).fix().local_minus_utc()
+}
+
+// --------------------------------------------------------------------------------
+
+/// UTC offset at the start of a period, followed by all offset changes within it
+pub type OffsetHistory = (i32, Vec<(DateTime<Utc>, i32)>);
+
+/// The offset changes described by an embedded VTIMEZONE, precomputed up to some horizon
+pub struct VTimezoneRules {
+    /// (onset in UTC, offset before, offset after), sorted by onset
+    onsets: Vec<(DateTime<Utc>, i32, i32)>,
+}
+
+impl VTimezoneRules {
+    /// Expand the STANDARD/DAYLIGHT observances of `vtimezone` up to `until`
+    pub fn new<C: Component>(vtimezone: &C, until: DateTime<Utc>) -> Self {
+	let mut onsets = vec![];
+	for observance in vtimezone.components() {
+	    let kind = observance.component_kind();
+	    if kind != "STANDARD" && kind != "DAYLIGHT" {
+		continue;
+	    }
+	    let (Some(from), Some(to)) = (observance.property_value("TZOFFSETFROM").and_then(parse_utc_offset),
+					  observance.property_value("TZOFFSETTO").and_then(parse_utc_offset)) else {
+		continue;
+	    };
+	    // Observance times are local wall-clock times in the "from" offset; expand them as if they were UTC
+	    let Some(start) = observance.property_value("DTSTART")
+		.and_then(|v| recurrence::parse_date_time_value(v, Tz::UTC)) else {
+		continue;
+	    };
+	    let mut set = RRuleSet::new(start);
+	    if let Some(rrule) = observance.property_value("RRULE").map(|rrule| wall_clock_rule(rrule, from)) {
+		if let Ok(rrule) = rrule.parse::<RRule<Unvalidated>>().and_then(|r| r.validate(start)) {
+		    set = set.rrule(rrule);
+		}
+	    }
+	    if set.get_rrule().is_empty() {
+		set = set.rdate(start);
+	    }
+	    for rdate in observance.multi_properties().get("RDATE").into_iter().flatten()
+		.chain(observance.properties().get("RDATE")) {
+		for value in rdate.value().split(',') {
+		    if let Some(local) = recurrence::parse_date_time_value(value.trim(), Tz::UTC) {
+			set = set.rdate(local);
+		    }
+		}
+	    }
+	    let horizon = (until + Duration::days(1)).with_timezone(&Tz::UTC);
+	    for local in set.before(horizon).all(u16::MAX).dates {
+		onsets.push((local.with_timezone(&Utc) - Duration::seconds(from as i64), from, to));
+	    }
+	}
+	onsets.sort_by_key(|(onset, _, _)| *onset);
+	Self {
+	    onsets,
+	}
+    }
+
+    /// Initial offset at `from` and all actual offset changes in [from, until)
+    pub fn changes(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> Option<OffsetHistory> {
+	let initial = self.offset_at(from)?;
+	let mut current = initial;
+	let mut changes = vec![];
+	for (onset, _, to) in self.onsets.iter().filter(|(onset, _, _)| *onset > from && *onset < until) {
+	    if *to != current {
+		changes.push((*onset, *to));
+		current = *to;
+	    }
+	}
+	Some((initial, changes))
+    }
+
+    /// Offset in effect at `t`, if the definition contains any usable observance
+    pub fn offset_at(&self, t: DateTime<Utc>) -> Option<i32> {
+	let index = self.onsets.partition_point(|(onset, _, _)| *onset <= t);
+	if index == 0 {
+	    self.onsets.first().map(|(_, from, _)| *from)
+	} else {
+	    Some(self.onsets[index - 1].2)
+	}
+    }
+}
+
+/// An observance's RRULE with its UNTIL, a UTC time, shifted by the offset before the onsets, like
+/// the onsets themselves when expanded as wall-clock times
+fn wall_clock_rule(rrule: &str, offset: i32) -> String {
+    rrule.split(';')
+	.map(|part| match part.split_once('=') {
+	    Some((key, until)) if key.eq_ignore_ascii_case("UNTIL") && until.ends_with('Z') => {
+		match recurrence::parse_date_time_value(until, Tz::UTC) {
+		    Some(until) => format!("{}={}", key, (until + Duration::seconds(offset as i64)).format("%Y%m%dT%H%M%SZ")),
+		    None        => part.to_string(),
+		}
+	    },
+	    _ => part.to_string(),
+	})
+	.collect::<Vec<_>>()
+	.join(";")
+}
+
+// --------------------------------------------------------------------------------
+
+/// A change of UTC offset (or of zone abbreviation / DST flag) in the IANA database
+struct Transition {
+    at: DateTime<Utc>,
+    from: i32,
+    to: i32,
+    name: String,
+    dst: bool,
+}
+
+/// (offset, abbreviation, is-DST) of a zone at a given time
+fn zone_state(tz: chrono_tz::Tz, t: DateTime<Utc>) -> (i32, String, bool) {
+    let offset = tz.offset_from_utc_datetime(&t.naive_utc());
+    let total = offset.fix().local_minus_utc();
+    let name = offset.abbreviation().map(|n| n.to_string()).unwrap_or_else(|| format_utc_offset(total));
+    (total, name, !offset.dst_offset().is_zero())
+}
+
+/// All transitions of `tz` in [from, until)
+fn iana_transitions(tz: chrono_tz::Tz, from: DateTime<Utc>, until: DateTime<Utc>) -> Vec<Transition> {
+    let mut transitions = vec![];
+    let mut t = from;
+    let mut state = zone_state(tz, t);
+    while t < until {
+	let next = t + Duration::days(1);
+	let next_state = zone_state(tz, next);
+	if next_state != state {
+	    // Bisect down to the second at which the change happens
+	    let (mut lo, mut hi) = (t, next);
+	    while hi - lo > Duration::seconds(1) {
+		let mid = lo + (hi - lo) / 2;
+		if zone_state(tz, mid) == state { lo = mid } else { hi = mid }
+	    }
+	    let (to, name, dst) = zone_state(tz, hi);
+	    transitions.push(Transition { at: hi, from: state.0, to, name, dst });
+	    state = zone_state(tz, hi);
+	    t = hi;
+	    continue;
+	}
+	state = next_state;
+	t = next;
+    }
+    transitions
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let next = if month == 12 { NaiveDate::from_ymd_opt(year + 1, 1, 1) } else { NaiveDate::from_ymd_opt(year, month + 1, 1) };
+    next.and_then(|n| n.pred_opt()).map(|d| d.day()).unwrap_or(31)
+}
+
+/// BYDAY rule ("-1SU", "2SU", ...) that matches this date
+fn byday_rule(date: NaiveDate) -> String {
+    let weekday = &date.weekday().to_string()[..2].to_uppercase();
+    if date.day() + 7 > days_in_month(date.year(), date.month()) {
+	format!("-1{}", weekday)
+    } else {
+	format!("{}{}", (date.day() - 1) / 7 + 1, weekday)
+    }
+}
+
+/// Consecutive yearly transitions that follow the same rule
+struct Run {
+    key: (bool, i32, i32, String, u32, String, chrono::NaiveTime),
+    first_local: NaiveDateTime,
+    last_at: DateTime<Utc>,
+    last_year: i32,
+    count: usize,
+}
+
+/// Generate the text of a VTIMEZONE for `tz`, named `tzid`, covering the years [from_year, to_year].
+/// Yearly rules are compressed into RRULEs; the last rule stays open-ended.
+pub fn generate_vtimezone(tzid: &str, tz: chrono_tz::Tz, from_year: i32, to_year: i32) -> String {
+    let from = Utc.with_ymd_and_hms(from_year, 1, 1, 0, 0, 0).unwrap();
+    let until = Utc.with_ymd_and_hms(to_year + 1, 1, 1, 0, 0, 0).unwrap();
+    let mut runs: Vec<Run> = vec![];
+    for transition in iana_transitions(tz, from, until) {
+	let local = transition.at.naive_utc() + Duration::seconds(transition.from as i64);
+	let key = (transition.dst, transition.from, transition.to, transition.name,
+		   local.month(), byday_rule(local.date()), local.time());
+	match runs.iter_mut().find(|r| r.key == key && r.last_year == local.year() - 1) {
+	    Some(run) => {
+		run.last_at = transition.at;
+		run.last_year = local.year();
+		run.count += 1;
+	    },
+	    None => runs.push(Run { key, first_local: local, last_at: transition.at, last_year: local.year(), count: 1 }),
+	}
+    }
+
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", tzid)];
+    if runs.is_empty() {
+	let (offset, name, _) = zone_state(tz, from);
+	lines.extend([
+	    "BEGIN:STANDARD".to_string(),
+	    "DTSTART:19700101T000000".to_string(),
+	    format!("TZNAME:{}", name),
+	    format!("TZOFFSETFROM:{}", format_utc_offset(offset)),
+	    format!("TZOFFSETTO:{}", format_utc_offset(offset)),
+	    "END:STANDARD".to_string(),
+	]);
+    }
+    for run in runs {
+	let (dst, from, to, name, month, byday, _) = run.key;
+	let kind = if dst { "DAYLIGHT" } else { "STANDARD" };
+	lines.push(format!("BEGIN:{}", kind));
+	lines.push(format!("DTSTART:{}", run.first_local.format("%Y%m%dT%H%M%S")));
+	if run.count > 1 {
+	    let until = if run.last_year < to_year {
+		format!(";UNTIL={}", run.last_at.format("%Y%m%dT%H%M%SZ"))
+	    } else { String::new() };
+	    lines.push(format!("RRULE:FREQ=YEARLY;BYMONTH={};BYDAY={}{}", month, byday, until));
+	}
+	lines.push(format!("TZNAME:{}", name));
+	lines.push(format!("TZOFFSETFROM:{}", format_utc_offset(from)));
+	lines.push(format!("TZOFFSETTO:{}", format_utc_offset(to)));
+	lines.push(format!("END:{}", kind));
+    }
+    lines.push("END:VTIMEZONE".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+/// Generate a VTIMEZONE component (see `generate_vtimezone`) spanning 1970 to ten years from now
+pub fn generate_vtimezone_component(tzid: &str, tz: chrono_tz::Tz) -> CalendarComponent {
+    generate_vtimezone(tzid, tz, 1970, Utc::now().year() + 10).parse()
+	.expect("generated VTIMEZONE must parse")
+}
+
+// --------------------------------------------------------------------------------
+
+/// Windows time zone names, as used by Outlook and Exchange, and their IANA equivalents
+/// (following the CLDR "windowsZones" mapping for the primary territory)
+const WINDOWS_ZONES: &[(&str, &str)] = &[
+    ("Dateline Standard Time", "Etc/GMT+12"),
+    ("UTC-11", "Etc/GMT+11"),
+    ("Aleutian Standard Time", "America/Adak"),
+    ("Hawaiian Standard Time", "Pacific/Honolulu"),
+    ("Marquesas Standard Time", "Pacific/Marquesas"),
+    ("Alaskan Standard Time", "America/Anchorage"),
+    ("UTC-09", "Etc/GMT+9"),
+    ("Pacific Standard Time (Mexico)", "America/Tijuana"),
+    ("UTC-08", "Etc/GMT+8"),
+    ("Pacific Standard Time", "America/Los_Angeles"),
+    ("US Mountain Standard Time", "America/Phoenix"),
+    ("Mountain Standard Time (Mexico)", "America/Mazatlan"),
+    ("Mountain Standard Time", "America/Denver"),
+    ("Yukon Standard Time", "America/Whitehorse"),
+    ("Central America Standard Time", "America/Guatemala"),
+    ("Central Standard Time", "America/Chicago"),
+    ("Easter Island Standard Time", "Pacific/Easter"),
+    ("Central Standard Time (Mexico)", "America/Mexico_City"),
+    ("Canada Central Standard Time", "America/Regina"),
+    ("SA Pacific Standard Time", "America/Bogota"),
+    ("Eastern Standard Time (Mexico)", "America/Cancun"),
+    ("Eastern Standard Time", "America/New_York"),
+    ("Haiti Standard Time", "America/Port-au-Prince"),
+    ("Cuba Standard Time", "America/Havana"),
+    ("US Eastern Standard Time", "America/Indiana/Indianapolis"),
+    ("Turks And Caicos Standard Time", "America/Grand_Turk"),
+    ("Paraguay Standard Time", "America/Asuncion"),
+    ("Atlantic Standard Time", "America/Halifax"),
+    ("Venezuela Standard Time", "America/Caracas"),
+    ("Central Brazilian Standard Time", "America/Cuiaba"),
+    ("SA Western Standard Time", "America/La_Paz"),
+    ("Pacific SA Standard Time", "America/Santiago"),
+    ("Newfoundland Standard Time", "America/St_Johns"),
+    ("Tocantins Standard Time", "America/Araguaina"),
+    ("E. South America Standard Time", "America/Sao_Paulo"),
+    ("SA Eastern Standard Time", "America/Cayenne"),
+    ("Argentina Standard Time", "America/Argentina/Buenos_Aires"),
+    ("Greenland Standard Time", "America/Nuuk"),
+    ("Montevideo Standard Time", "America/Montevideo"),
+    ("Magallanes Standard Time", "America/Punta_Arenas"),
+    ("Saint Pierre Standard Time", "America/Miquelon"),
+    ("Bahia Standard Time", "America/Bahia"),
+    ("UTC-02", "Etc/GMT+2"),
+    ("Azores Standard Time", "Atlantic/Azores"),
+    ("Cape Verde Standard Time", "Atlantic/Cape_Verde"),
+    ("UTC", "Etc/UTC"),
+    ("GMT Standard Time", "Europe/London"),
+    ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+    ("Sao Tome Standard Time", "Africa/Sao_Tome"),
+    ("Morocco Standard Time", "Africa/Casablanca"),
+    ("W. Europe Standard Time", "Europe/Berlin"),
+    ("Central Europe Standard Time", "Europe/Budapest"),
+    ("Romance Standard Time", "Europe/Paris"),
+    ("Central European Standard Time", "Europe/Warsaw"),
+    ("W. Central Africa Standard Time", "Africa/Lagos"),
+    ("Jordan Standard Time", "Asia/Amman"),
+    ("GTB Standard Time", "Europe/Bucharest"),
+    ("Middle East Standard Time", "Asia/Beirut"),
+    ("Egypt Standard Time", "Africa/Cairo"),
+    ("E. Europe Standard Time", "Europe/Chisinau"),
+    ("Syria Standard Time", "Asia/Damascus"),
+    ("West Bank Standard Time", "Asia/Hebron"),
+    ("South Africa Standard Time", "Africa/Johannesburg"),
+    ("FLE Standard Time", "Europe/Kyiv"),
+    ("Israel Standard Time", "Asia/Jerusalem"),
+    ("South Sudan Standard Time", "Africa/Juba"),
+    ("Kaliningrad Standard Time", "Europe/Kaliningrad"),
+    ("Sudan Standard Time", "Africa/Khartoum"),
+    ("Libya Standard Time", "Africa/Tripoli"),
+    ("Namibia Standard Time", "Africa/Windhoek"),
+    ("Arabic Standard Time", "Asia/Baghdad"),
+    ("Turkey Standard Time", "Europe/Istanbul"),
+    ("Arab Standard Time", "Asia/Riyadh"),
+    ("Belarus Standard Time", "Europe/Minsk"),
+    ("Russian Standard Time", "Europe/Moscow"),
+    ("E. Africa Standard Time", "Africa/Nairobi"),
+    ("Volgograd Standard Time", "Europe/Volgograd"),
+    ("Iran Standard Time", "Asia/Tehran"),
+    ("Arabian Standard Time", "Asia/Dubai"),
+    ("Astrakhan Standard Time", "Europe/Astrakhan"),
+    ("Azerbaijan Standard Time", "Asia/Baku"),
+    ("Russia Time Zone 3", "Europe/Samara"),
+    ("Mauritius Standard Time", "Indian/Mauritius"),
+    ("Saratov Standard Time", "Europe/Saratov"),
+    ("Georgian Standard Time", "Asia/Tbilisi"),
+    ("Caucasus Standard Time", "Asia/Yerevan"),
+    ("Afghanistan Standard Time", "Asia/Kabul"),
+    ("West Asia Standard Time", "Asia/Tashkent"),
+    ("Ekaterinburg Standard Time", "Asia/Yekaterinburg"),
+    ("Pakistan Standard Time", "Asia/Karachi"),
+    ("Qyzylorda Standard Time", "Asia/Qyzylorda"),
+    ("India Standard Time", "Asia/Kolkata"),
+    ("Sri Lanka Standard Time", "Asia/Colombo"),
+    ("Nepal Standard Time", "Asia/Kathmandu"),
+    ("Central Asia Standard Time", "Asia/Almaty"),
+    ("Bangladesh Standard Time", "Asia/Dhaka"),
+    ("Omsk Standard Time", "Asia/Omsk"),
+    ("Myanmar Standard Time", "Asia/Yangon"),
+    ("SE Asia Standard Time", "Asia/Bangkok"),
+    ("Altai Standard Time", "Asia/Barnaul"),
+    ("W. Mongolia Standard Time", "Asia/Hovd"),
+    ("North Asia Standard Time", "Asia/Krasnoyarsk"),
+    ("N. Central Asia Standard Time", "Asia/Novosibirsk"),
+    ("Tomsk Standard Time", "Asia/Tomsk"),
+    ("China Standard Time", "Asia/Shanghai"),
+    ("North Asia East Standard Time", "Asia/Irkutsk"),
+    ("Singapore Standard Time", "Asia/Singapore"),
+    ("W. Australia Standard Time", "Australia/Perth"),
+    ("Taipei Standard Time", "Asia/Taipei"),
+    ("Ulaanbaatar Standard Time", "Asia/Ulaanbaatar"),
+    ("Aus Central W. Standard Time", "Australia/Eucla"),
+    ("Transbaikal Standard Time", "Asia/Chita"),
+    ("Tokyo Standard Time", "Asia/Tokyo"),
+    ("North Korea Standard Time", "Asia/Pyongyang"),
+    ("Korea Standard Time", "Asia/Seoul"),
+    ("Yakutsk Standard Time", "Asia/Yakutsk"),
+    ("Cen. Australia Standard Time", "Australia/Adelaide"),
+    ("AUS Central Standard Time", "Australia/Darwin"),
+    ("E. Australia Standard Time", "Australia/Brisbane"),
+    ("AUS Eastern Standard Time", "Australia/Sydney"),
+    ("West Pacific Standard Time", "Pacific/Port_Moresby"),
+    ("Tasmania Standard Time", "Australia/Hobart"),
+    ("Vladivostok Standard Time", "Asia/Vladivostok"),
+    ("Lord Howe Standard Time", "Australia/Lord_Howe"),
+    ("Bougainville Standard Time", "Pacific/Bougainville"),
+    ("Russia Time Zone 10", "Asia/Srednekolymsk"),
+    ("Magadan Standard Time", "Asia/Magadan"),
+    ("Norfolk Standard Time", "Pacific/Norfolk"),
+    ("Sakhalin Standard Time", "Asia/Sakhalin"),
+    ("Central Pacific Standard Time", "Pacific/Guadalcanal"),
+    ("Russia Time Zone 11", "Asia/Kamchatka"),
+    ("New Zealand Standard Time", "Pacific/Auckland"),
+    ("UTC+12", "Etc/GMT-12"),
+    ("Fiji Standard Time", "Pacific/Fiji"),
+    ("Chatham Islands Standard Time", "Pacific/Chatham"),
+    ("UTC+13", "Etc/GMT-13"),
+    ("Tonga Standard Time", "Pacific/Tongatapu"),
+    ("Samoa Standard Time", "Pacific/Apia"),
+    ("Line Islands Standard Time", "Pacific/Kiritimati"),
+];
+
+/// Former names of the IANA time zones in `WINDOWS_ZONES`, which still parse as (links to) them
+const LEGACY_NAMES: &[(&str, &str)] = &[
+    ("Asia/Calcutta", "Asia/Kolkata"),
+    ("Europe/Kiev", "Europe/Kyiv"),
+    ("America/Godthab", "America/Nuuk"),
+    ("America/Buenos_Aires", "America/Argentina/Buenos_Aires"),
+    ("Asia/Katmandu", "Asia/Kathmandu"),
+    ("Asia/Rangoon", "Asia/Yangon"),
+    ("America/Indianapolis", "America/Indiana/Indianapolis"),
+    ("UTC", "Etc/UTC"),
+];
+
+/// Windows time zone name for an IANA time zone, if it is the primary zone of one
+pub fn windows_name(tz: chrono_tz::Tz) -> Option<&'static str> {
+    let name = LEGACY_NAMES.iter()
+	.find(|(legacy, _)| *legacy == tz.name())
+	.map_or(tz.name(), |(_, current)| *current);
+    WINDOWS_ZONES.iter()
+	.find(|(_, iana)| *iana == name)
+	.map(|(windows, _)| *windows)
+}
+
+/// IANA time zone for a Windows time zone name
+pub fn windows_zone(name: &str) -> Option<chrono_tz::Tz> {
+    WINDOWS_ZONES.iter()
+	.find(|(windows, _)| windows.eq_ignore_ascii_case(name))
+	.and_then(|(_, iana)| iana.parse().ok())
+}
+
+// --------------------------------------------------------------------------------
+
+/// TZIDs referenced by parameters of any property in any (sub-)component
+pub fn referenced_tzids<C: Component>(component: &C, tzids: &mut Vec<String>) {
+    for property in component.properties().values().chain(component.multi_properties().values().flatten()) {
+	if let Some(tzid) = property.params().get("TZID") {
+	    if !tzids.iter().any(|t| t == tzid.value()) {
+		tzids.push(tzid.value().to_string());
+	    }
+	}
+    }
+    for child in component.components() {
+	referenced_tzids(child, tzids);
+    }
+}
+
+/// TZID of a top-level VTIMEZONE component
+pub fn vtimezone_tzid(component: &CalendarComponent) -> Option<&str> {
+    match component {
+	CalendarComponent::Other(other) if other.component_kind() == "VTIMEZONE" => other.property_value("TZID"),
+	_ => None,
+    }
+}
+
+/// Drop the VTIMEZONE definitions that no other component of the calendar refers to
+pub fn retain_referenced_timezones(calendar: &mut Calendar) {
+    let mut tzids = vec![];
+    for component in &calendar.components {
+	match component {
+	    CalendarComponent::Event(event) => referenced_tzids(event, &mut tzids),
+	    CalendarComponent::Todo(todo)   => referenced_tzids(todo, &mut tzids),
+	    CalendarComponent::Venue(venue) => referenced_tzids(venue, &mut tzids),
+	    CalendarComponent::Other(other) if vtimezone_tzid(component).is_none() => referenced_tzids(other, &mut tzids),
+	    _ => {},
+	}
+    }
+    calendar.components.retain(|c| vtimezone_tzid(c).is_none_or(|tzid| tzids.iter().any(|t| t == tzid)));
+}
+
+/// Replace all VTIMEZONE definitions of known time zones (IANA zones, also under vendor-prefixed or
+/// Windows names) in place by definitions generated from the IANA database, keeping their TZIDs.
+/// Returns the TZIDs of the definitions that could not be replaced.
+pub fn replace_timezone_definitions(calendar: &mut Calendar) -> Vec<String> {
+    let mut unknown = vec![];
+    for component in calendar.components.iter_mut() {
+	let Some(tzid) = vtimezone_tzid(component).map(|tzid| tzid.to_string()) else {
+	    continue;
+	};
+	match recurrence::resolve_tz(&tzid) {
+	    Some(tz) => *component = generate_vtimezone_component(&tzid, tz),
+	    None     => unknown.push(tzid),
+	}
+    }
+    unknown
+}
+
+/// Add VTIMEZONE definitions for all IANA time zones referenced in the calendar that lack one.
+/// With `force`, also replace existing definitions of IANA zones.
+pub fn embed_timezones(calendar: &mut Calendar, force: bool) {
+    let mut tzids = vec![];
+    for component in &calendar.components {
+	match component {
+	    CalendarComponent::Event(event) => referenced_tzids(event, &mut tzids),
+	    CalendarComponent::Todo(todo)   => referenced_tzids(todo, &mut tzids),
+	    _ => {},
+	}
+    }
+    let defined: HashSet<String> = calendar.components.iter()
+	.filter_map(|c| vtimezone_tzid(c).map(|tzid| tzid.to_string()))
+	.collect();
+
+    if force {
+	replace_timezone_definitions(calendar);
+    }
+
+    // Add missing definitions in front of the events that use them
+    let mut missing = vec![];
+    for tzid in tzids.iter().filter(|tzid| !defined.contains(*tzid)) {
+	match recurrence::resolve_tz(tzid) {
+	    Some(tz) => missing.push(generate_vtimezone_component(tzid, tz)),
+	    None     => crate::warn(format!("No definition for unknown time zone '{}'", tzid)),
+	}
+    }
+    calendar.components.splice(0..0, missing);
+}
+
+// --------------------------------------------------------------------------------
+
+/// Groups of TZIDs whose VTIMEZONE definitions describe the same offsets (over the years in
+/// which the calendar has events, and the next few years).  The preferred name, an IANA
+/// name if there is one, comes first in each group.
+pub fn equivalent_timezones(calendar: &Calendar) -> Vec<Vec<String>> {
+    let this_year = Utc::now().year();
+    let first_year = calendar.components.iter()
+	.filter_map(|c| c.as_event())
+	.filter_map(|event| event.property_value("DTSTART"))
+	.filter_map(|dtstart| dtstart.get(..4)?.parse::<i32>().ok())
+	.chain([this_year - 1])
+	.min().unwrap().max(1970);
+    let from = Utc.with_ymd_and_hms(first_year, 1, 1, 0, 0, 0).unwrap();
+    let until = Utc.with_ymd_and_hms(this_year + 5, 1, 1, 0, 0, 0).unwrap();
+
+    let mut groups: Vec<(OffsetHistory, Vec<String>)> = vec![];
+    for component in &calendar.components {
+	let (CalendarComponent::Other(vtimezone), Some(tzid)) = (component, vtimezone_tzid(component)) else {
+	    continue;
+	};
+	let Some(signature) = VTimezoneRules::new(vtimezone, until).changes(from, until) else {
+	    continue;
+	};
+	match groups.iter_mut().find(|(s, _)| *s == signature) {
+	    Some((_, names)) => names.push(tzid.to_string()),
+	    None             => groups.push((signature, vec![tzid.to_string()])),
+	}
+    }
+    groups.into_iter()
+	.map(|(_, mut names)| {
+	    if let Some(iana) = names.iter().position(|n| n.parse::<chrono_tz::Tz>().is_ok()) {
+		names[..=iana].rotate_right(1);
+	    }
+	    names
+	})
+	.filter(|names| names.len() > 1)
+	.collect()
+}
+
+/// Unify equivalent VTIMEZONEs (see `equivalent_timezones`): keep one definition per group and
+/// rewrite all TZID references to it.  Returns the (replaced, preferred) names.
+pub fn merge_equivalent_timezones(calendar: &mut Calendar) -> Vec<(String, String)> {
+    let mut renaming = HashMap::new();
+    for group in equivalent_timezones(calendar) {
+	for name in &group[1..] {
+	    renaming.insert(name.clone(), group[0].clone());
+	}
+    }
+    if renaming.is_empty() {
+	return vec![];
+    }
+
+    let rename = |property: &icalendar::Property| {
+	let mut property = property.clone();
+	let new_tzid = property.params().get("TZID").and_then(|tzid| renaming.get(tzid.value()));
+	if let Some(new_tzid) = new_tzid.cloned() {
+	    property.add_parameter("TZID", &new_tzid);
+	}
+	Some(property)
+    };
+    calendar.components.retain(|c| !vtimezone_tzid(c).is_some_and(|tzid| renaming.contains_key(tzid)));
+    // All components, such as VJOURNAL, and their sub-components, such as VALARM, may refer to a
+    // time zone
+    for component in calendar.components.iter_mut() {
+	if vtimezone_tzid(component).is_none() {
+	    *component = crate::map_component_properties(component, rename);
+	}
+    }
+    let mut merged: Vec<_> = renaming.into_iter().collect();
+    merged.sort();
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_utc_offsets() {
+	assert_eq!(parse_utc_offset("+0200"), Some(7200));
+	assert_eq!(parse_utc_offset("-0330"), Some(-12600));
+	assert_eq!(parse_utc_offset("+004510"), Some(2710));
+	assert_eq!(parse_utc_offset("0200"), None);
+	assert_eq!(parse_utc_offset("+0260"), None);
+	assert_eq!(parse_utc_offset("+02:00"), None);
+	for offset in [7200, -12600, 2710, 0] {
+	    assert_eq!(parse_utc_offset(&format_utc_offset(offset)), Some(offset));
+	}
+    }
+
+    #[test]
+    fn names_transition_days_by_weekday() {
+	// Last Sunday in March 2025, second Sunday in March 2025
+	assert_eq!(byday_rule(NaiveDate::from_ymd_opt(2025, 3, 30).unwrap()), "-1SU");
+	assert_eq!(byday_rule(NaiveDate::from_ymd_opt(2025, 3, 9).unwrap()), "2SU");
+	assert_eq!(byday_rule(NaiveDate::from_ymd_opt(2025, 4, 1).unwrap()), "1TU");
+    }
+
+    /// The offsets of a generated definition agree with the IANA database throughout its range
+    fn assert_matches_iana(tz: chrono_tz::Tz) {
+	let CalendarComponent::Other(vtimezone) = generate_vtimezone_component(tz.name(), tz) else {
+	    panic!("not a VTIMEZONE");
+	};
+	assert_eq!(vtimezone.property_value("TZID"), Some(tz.name()));
+	let rules = VTimezoneRules::new(&vtimezone, Utc.with_ymd_and_hms(2031, 1, 1, 0, 0, 0).unwrap());
+	let mut t = Utc.with_ymd_and_hms(1990, 1, 1, 12, 0, 0).unwrap();
+	while t.year() < 2031 {
+	    assert_eq!(rules.offset_at(t), Some(iana_offset(tz, t)), "{} at {}", tz.name(), t);
+	    t += Duration::days(5);
+	}
+    }
+
+    #[test]
+    fn generates_definitions_that_match_the_iana_database() {
+	assert_matches_iana(chrono_tz::Europe::Berlin);
+	assert_matches_iana(chrono_tz::America::New_York);
+	assert_matches_iana(chrono_tz::Australia::Sydney);
+	assert_matches_iana(chrono_tz::Asia::Kolkata);
+    }
+
+    #[test]
+    fn compresses_yearly_rules() {
+	let text = generate_vtimezone("Europe/Berlin", chrono_tz::Europe::Berlin, 2000, 2030);
+	assert!(text.starts_with("BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\n"));
+	assert_eq!(text.matches("BEGIN:DAYLIGHT").count(), 1);
+	assert_eq!(text.matches("BEGIN:STANDARD").count(), 1);
+	// Open-ended, as the rules still apply at the end of the range
+	assert!(text.contains("RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r\n"));
+	assert!(text.contains("RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r\n"));
+	assert!(text.contains("TZNAME:CEST\r\nTZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\n"));
+    }
+
+    #[test]
+    fn generates_a_single_observance_for_zones_without_transitions() {
+	let text = generate_vtimezone("Asia/Kolkata", chrono_tz::Asia::Kolkata, 2000, 2030);
+	assert!(text.contains("BEGIN:STANDARD\r\nDTSTART:19700101T000000\r\n"));
+	assert!(text.contains("TZOFFSETFROM:+0530\r\nTZOFFSETTO:+0530\r\n"));
+	assert!(!text.contains("RRULE"));
+    }
+
+    #[test]
+    fn maps_windows_zone_names_both_ways() {
+	assert_eq!(windows_zone("W. Europe Standard Time"), Some(chrono_tz::Europe::Berlin));
+	assert_eq!(windows_zone("pacific standard time"), Some(chrono_tz::America::Los_Angeles));
+	assert_eq!(windows_zone("Atlantis Standard Time"), None);
+	assert_eq!(windows_name(chrono_tz::Europe::Berlin), Some("W. Europe Standard Time"));
+	// Legacy names of the primary zones
+	assert_eq!(windows_name(chrono_tz::Asia::Calcutta), Some("India Standard Time"));
+	assert_eq!(windows_name(chrono_tz::Europe::Kiev), windows_name(chrono_tz::Europe::Kyiv));
+	// Other zones of the same Windows zone
+	assert_eq!(windows_name(chrono_tz::Europe::Amsterdam), None);
+	// Every IANA name in the table exists
+	for (windows, _) in WINDOWS_ZONES {
+	    assert!(windows_zone(windows).is_some(), "{}", windows);
+	}
+    }
+}
@@ -0,0 +1,231 @@
+// Birthdays and anniversaries from vCard (.vcf) files
+
+use chrono::{NaiveDate, Utc};
+use icalendar::{Component, Event, Property};
+
+/// A date from a vCard; the year is often omitted ("--0412")
+struct Occasion {
+    year: Option<i32>,
+    month: u32,
+    day: u32,
+}
+
+pub struct Card {
+    name: String,
+    uid: Option<String>,
+    birthday: Option<Occasion>,
+    anniversary: Option<Occasion>,
+}
+
+/// Undo vCard TEXT escaping
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+	if c == '\\' {
+	    match chars.next() {
+		Some('n') | Some('N') => result.push('\n'),
+		Some(c)               => result.push(c),
+		None                  => {},
+	    }
+	} else {
+	    result.push(c);
+	}
+    }
+    result
+}
+
+/// Parse "19850412", "1985-04-12", "--0412", "--04-12", or any of these followed by a time
+fn parse_occasion(value: &str) -> Option<Occasion> {
+    let date = value.split('T').next()?.replace('-', "");
+    let (year, month_day) = if value.starts_with("--") {
+	(None, date.as_str())
+    } else if date.len() == 8 {
+	(Some(date[..4].parse().ok()?), &date[4..])
+    } else {
+	return None;
+    };
+    if month_day.len() != 4 {
+	return None;
+    }
+    let month = month_day[..2].parse().ok()?;
+    let day = month_day[2..].parse().ok()?;
+    // Validate against a leap year, so that February 29 passes
+    NaiveDate::from_ymd_opt(year.unwrap_or(2000), month, day)?;
+    Some(Occasion { year, month, day })
+}
+
+/// All cards in a vCard file
+pub fn parse_vcards(text: &str) -> Vec<Card> {
+    let unfolded = text.replace("\r\n ", "").replace("\r\n\t", "").replace("\n ", "").replace("\n\t", "");
+    let mut cards = vec![];
+    let mut current: Option<(Card, Option<String>)> = None;
+    for line in unfolded.lines() {
+	let Some((name_params, value)) = line.split_once(':') else {
+	    continue;
+	};
+	// Drop parameters and group prefixes ("item1.X-ABDATE")
+	let key = name_params.split(';').next().unwrap_or("").rsplit('.').next().unwrap_or("").to_uppercase();
+	match (key.as_str(), &mut current) {
+	    ("BEGIN", None) if value.eq_ignore_ascii_case("VCARD") => {
+		current = Some((Card { name: String::new(), uid: None, birthday: None, anniversary: None }, None));
+	    },
+	    ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+		let (mut card, structured_name) = current.take().unwrap();
+		if card.name.is_empty() {
+		    card.name = structured_name.unwrap_or_default();
+		}
+		cards.push(card);
+	    },
+	    ("FN", Some((card, _)))  => card.name = unescape(value).trim().to_string(),
+	    ("N", Some((_, name)))   => {
+		// Family;Given;Additional;Prefixes;Suffixes
+		let parts: Vec<String> = value.split(';').map(unescape).collect();
+		let given_family = [parts.get(1), parts.first()].into_iter().flatten()
+		    .filter(|p| !p.is_empty()).cloned().collect::<Vec<_>>().join(" ");
+		*name = Some(given_family);
+	    },
+	    ("UID", Some((card, _))) => card.uid = Some(value.to_string()),
+	    ("BDAY", Some((card, _))) => card.birthday = parse_occasion(value),
+	    ("ANNIVERSARY" | "X-ANNIVERSARY" | "X-EVOLUTION-ANNIVERSARY", Some((card, _))) => {
+		card.anniversary = parse_occasion(value);
+	    },
+	    _ => {},
+	}
+    }
+    cards
+}
+
+/// Yearly all-day events for the birthday and anniversary of a card
+pub fn occasion_events(card: &Card) -> Vec<Event> {
+    let occasions = [("Birthday", "bday", &card.birthday), ("Anniversary", "anniversary", &card.anniversary)];
+    let mut events = vec![];
+    for (title, tag, occasion) in occasions {
+	let Some(occasion) = occasion else {
+	    continue;
+	};
+	// Without a known year, start in a leap year (so that February 29 exists)
+	let year = occasion.year.unwrap_or(2000);
+	let start = NaiveDate::from_ymd_opt(year, occasion.month, occasion.day)
+	    .or_else(|| NaiveDate::from_ymd_opt(2000, occasion.month, occasion.day))
+	    .unwrap();
+	let rrule = if (occasion.month, occasion.day) == (2, 29) {
+	    // Celebrated on February 28 in common years
+	    "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1".to_string()
+	} else {
+	    "FREQ=YEARLY".to_string()
+	};
+	let id = card.uid.clone().unwrap_or_else(|| card.name.to_lowercase().replace(char::is_whitespace, "-"));
+
+	let mut event = Event::new();
+	event.uid(&format!("{}-{}@icalm", tag, id));
+	event.summary(&format!("{}: {}", title, card.name));
+	event.timestamp(Utc::now());
+	let mut dtstart = Property::new("DTSTART", start.format("%Y%m%d").to_string());
+	dtstart.add_parameter("VALUE", "DATE");
+	event.append_property(dtstart);
+	let mut dtend = Property::new("DTEND", start.succ_opt().unwrap().format("%Y%m%d").to_string());
+	dtend.add_parameter("VALUE", "DATE");
+	event.append_property(dtend);
+	event.add_property("RRULE", &rrule);
+	event.add_property("TRANSP", "TRANSPARENT");
+	event.add_multi_property("CATEGORIES", title);
+	events.push(event);
+    }
+    events
+}
+
+/// vCard TEXT escaping
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace(';', "\\;").replace('\n', "\\n")
+}
+
+/// Minimal vCard 3.0 for a contact
+pub fn format_vcard(name: Option<&str>, email: &str) -> String {
+    let full_name = name.unwrap_or(email);
+    // Best guess at "Family;Given" from "Given Family"
+    let structured = match name.and_then(|name| name.rsplit_once(' ')) {
+	Some((given, family)) => format!("{};{};;;", escape(family), escape(given)),
+	None                  => format!("{};;;;", escape(full_name)),
+    };
+    ["BEGIN:VCARD", "VERSION:3.0",
+     &format!("FN:{}", escape(full_name)),
+     &format!("N:{}", structured),
+     &format!("EMAIL;TYPE=INTERNET:{}", email),
+     "END:VCARD"].join("\r\n") + "\r\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dates_with_and_without_year() {
+	let date = |value| parse_occasion(value).map(|o| (o.year, o.month, o.day));
+	assert_eq!(date("19850412"), Some((Some(1985), 4, 12)));
+	assert_eq!(date("1985-04-12T00:00:00Z"), Some((Some(1985), 4, 12)));
+	assert_eq!(date("--0412"), Some((None, 4, 12)));
+	assert_eq!(date("--04-12"), Some((None, 4, 12)));
+	assert_eq!(date("--0229"), Some((None, 2, 29)));
+	assert_eq!(date("19850230"), None);
+	assert_eq!(date("1985"), None);
+    }
+
+    #[test]
+    fn parses_cards() {
+	let cards = parse_vcards("\
+BEGIN:VCARD\r
+VERSION:3.0\r
+FN:Jane Doe\\, PhD\r
+UID:jane-1\r
+BDAY:1985-04-12\r
+item1.X-ABDATE:2010-06-01\r
+ANNIVERSARY:20100601\r
+END:VCARD\r
+BEGIN:VCARD\r
+VERSION:4.0\r
+N:Smith;John;;;\r
+BDAY;VALUE=date:--02\r
+ 29\r
+END:VCARD\r
+");
+	assert_eq!(cards.len(), 2);
+	assert_eq!(cards[0].name, "Jane Doe, PhD");
+	assert_eq!(cards[0].uid.as_deref(), Some("jane-1"));
+	assert!(cards[0].birthday.is_some() && cards[0].anniversary.is_some());
+	// Name from N, and a folded line
+	assert_eq!(cards[1].name, "John Smith");
+	assert_eq!(cards[1].birthday.as_ref().map(|o| (o.month, o.day)), Some((2, 29)));
+    }
+
+    #[test]
+    fn creates_yearly_all_day_events() {
+	let card = &parse_vcards("BEGIN:VCARD\nFN:Jane Doe\nBDAY:--0412\nEND:VCARD\n")[0];
+	let events = occasion_events(card);
+	assert_eq!(events.len(), 1);
+	let event = &events[0];
+	assert_eq!(event.get_uid(), Some("bday-jane-doe@icalm"));
+	assert_eq!(event.get_summary(), Some("Birthday: Jane Doe"));
+	assert_eq!(event.property_value("DTSTART"), Some("20000412"));
+	assert_eq!(event.property_value("DTEND"), Some("20000413"));
+	assert_eq!(event.property_value("RRULE"), Some("FREQ=YEARLY"));
+    }
+
+    #[test]
+    fn celebrates_leap_day_birthdays_in_common_years() {
+	let card = &parse_vcards("BEGIN:VCARD\nFN:Leap\nBDAY:19960229\nEND:VCARD\n")[0];
+	let event = &occasion_events(card)[0];
+	assert_eq!(event.property_value("DTSTART"), Some("19960229"));
+	assert_eq!(event.property_value("RRULE"), Some("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1"));
+    }
+
+    #[test]
+    fn formats_contacts() {
+	let card = format_vcard(Some("Jane van Doe"), "jane@example.com");
+	assert!(card.contains("FN:Jane van Doe\r\n"));
+	assert!(card.contains("N:Doe;Jane van;;;\r\n"));
+	assert!(card.contains("EMAIL;TYPE=INTERNET:jane@example.com\r\n"));
+	assert!(format_vcard(None, "a@b.c").contains("N:a@b.c;;;;\r\n"));
+	assert_eq!(escape("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+    }
+}